@@ -0,0 +1,20 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn emmental(c: &mut Criterion) {
+    let mut group = c.benchmark_group("emmental");
+    for workload in benchmarks::emmental() {
+        group.bench_function(workload.name, |b| b.iter(|| black_box(workload.run())));
+    }
+    group.finish();
+}
+
+fn mascarpone(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mascarpone");
+    for workload in benchmarks::mascarpone() {
+        group.bench_function(workload.name, |b| b.iter(|| black_box(workload.run())));
+    }
+    group.finish();
+}
+
+criterion_group!(languages, emmental, mascarpone);
+criterion_main!(languages);
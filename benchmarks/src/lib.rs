@@ -0,0 +1,149 @@
+//! Canonical workloads for benchmarking the interpreters in this workspace, see
+//! `benches/languages.rs`. They're kept here so their results can be tested, which
+//! makes sure a benchmark still measures what it's supposed to after a change.
+//!
+//! To compare a change against the current state of things, run
+//! `cargo bench -p benchmarks -- --save-baseline before` before making it, and
+//! `cargo bench -p benchmarks -- --baseline before` after.
+
+use mascarpone::budget::Budget;
+
+/// How often the short programs are repeated to make the long ones.
+const REPETITIONS: usize = 100;
+
+/// The number of steps the non-terminating Mascarpone programs are run for.
+const LOOP_STEPS: u64 = 100_000;
+
+const EMMENTAL_HELLO_WORLD: &[u8] =
+    b"#0#10#33#100#108#114#111#119#32#44#111#108#108#101#72...............";
+const EMMENTAL_HELLO_WORLD_FANCY: &[u8] = b";#58#126#63#36!;#46#36#!;#0#1!;#0#2!;#0#3!;#0#4!;#0#5!;#0#6!;#0#7!#0#10#33#100#108#114#111#119#32#44#111#108#108#101#72$";
+const EMMENTAL_PARITY: &[u8] = b"#59#94#118#58!#59#35#54#57#46#!#59#35#55#57#46#128!#59#58#43#58#43#58#43#58#43#58#43#58#43#58#43#109!,m?";
+
+const MASCARPONE_QUOTE: &str = "[o[ll]eh].........";
+
+pub struct Emmental {
+    pub name: &'static str,
+    pub program: Vec<u8>,
+    pub input: Vec<u8>,
+}
+
+impl Emmental {
+    pub fn run(&self) -> Result<Vec<u8>, emmental::Error> {
+        emmental::run_with_input(&self.program, &self.input)
+    }
+}
+
+pub fn emmental() -> Vec<Emmental> {
+    vec![
+        Emmental {
+            name: "hello_world",
+            program: EMMENTAL_HELLO_WORLD.to_vec(),
+            input: Vec::new(),
+        },
+        Emmental {
+            name: "hello_world_fancy",
+            program: EMMENTAL_HELLO_WORLD_FANCY.to_vec(),
+            input: Vec::new(),
+        },
+        Emmental {
+            name: "parity",
+            program: EMMENTAL_PARITY.to_vec(),
+            input: b"7".to_vec(),
+        },
+        Emmental {
+            name: "hello_world_repeated",
+            program: EMMENTAL_HELLO_WORLD.repeat(REPETITIONS),
+            input: Vec::new(),
+        },
+        // Redefines the same operations over and over, so this mostly measures `!`.
+        Emmental {
+            name: "hello_world_fancy_repeated",
+            program: EMMENTAL_HELLO_WORLD_FANCY.repeat(REPETITIONS),
+            input: Vec::new(),
+        },
+    ]
+}
+
+pub struct Mascarpone {
+    pub name: &'static str,
+    pub program: String,
+    pub input: String,
+    /// The number of steps to run for, if the program doesn't end by itself.
+    pub steps: Option<u64>,
+}
+
+impl Mascarpone {
+    pub fn run(&self) -> mascarpone::Result<String> {
+        let budget = self.steps.map_or_else(Budget::unlimited, Budget::steps);
+        mascarpone::compute_with_budget(&self.program, &self.input, budget)
+    }
+}
+
+pub fn mascarpone() -> Vec<Mascarpone> {
+    vec![
+        Mascarpone {
+            name: "quote",
+            program: MASCARPONE_QUOTE.to_owned(),
+            input: String::new(),
+            steps: None,
+        },
+        Mascarpone {
+            name: "quote_repeated",
+            program: MASCARPONE_QUOTE.repeat(REPETITIONS),
+            input: String::new(),
+            steps: None,
+        },
+        // An operation that performs itself as a tail call, so this doesn't grow.
+        Mascarpone {
+            name: "tail_loop",
+            program: "[:!]v*:!".to_owned(),
+            input: String::new(),
+            steps: Some(LOOP_STEPS),
+        },
+        // Like `tail_loop`, but every iteration leaves a frame behind.
+        Mascarpone {
+            name: "deep_recursion",
+            program: "[:!$]v*:!".to_owned(),
+            input: String::new(),
+            steps: Some(LOOP_STEPS),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use mascarpone::Error;
+
+    use super::*;
+
+    #[test]
+    fn emmental_workloads_produce_expected_output() {
+        for workload in emmental() {
+            let expected = match workload.name {
+                "hello_world" => b"Hello, world!\n\0".to_vec(),
+                "hello_world_fancy" => b"Hello, world!\n".to_vec(),
+                "parity" => b"O".to_vec(),
+                "hello_world_repeated" => b"Hello, world!\n\0".repeat(REPETITIONS),
+                "hello_world_fancy_repeated" => b"Hello, world!\n".repeat(REPETITIONS),
+                name => panic!("no expected output for {}", name),
+            };
+
+            assert_eq!(workload.run().unwrap(), expected, "{}", workload.name);
+        }
+    }
+
+    #[test]
+    fn mascarpone_workloads_produce_expected_output() {
+        for workload in mascarpone() {
+            let result = workload.run();
+            match workload.name {
+                "quote" => assert_eq!(result.unwrap(), "]he]ll[o["),
+                "quote_repeated" => assert_eq!(result.unwrap(), "]he]ll[o[".repeat(REPETITIONS)),
+                "tail_loop" | "deep_recursion" => {
+                    assert!(matches!(result.unwrap_err().root(), Error::OutOfBudget))
+                }
+                name => panic!("no expected output for {}", name),
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::Symbol;
+use crate::{native::Native, Symbol};
 
 #[derive(Debug, Clone, Copy)]
 pub enum PrimOp {
@@ -19,10 +19,32 @@ pub enum PrimOp {
     Semicolon, // ;
 }
 
+impl PrimOp {
+    /// What the operation does, in a sentence.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Nul => "Push 0.",
+            Self::Digit(_) => "Pop a symbol, multiply it by 10, add this digit and push the result.",
+            Self::Add => "Pop two symbols and push their sum.",
+            Self::Sub => "Pop two symbols and push the first minus the second.",
+            Self::Log2 => "Pop a symbol and push its binary logarithm, rounded down, or 8 for 0.",
+            Self::Output => "Pop a symbol and output it.",
+            Self::Input => "Read a symbol and push it, or 4 (end of transmission) if the input has run out.",
+            Self::Enqueue => "Enqueue the symbol on top of the stack, without popping it.",
+            Self::Dequeue => "Dequeue a symbol and push it.",
+            Self::Duplicate => "Duplicate the symbol on top of the stack.",
+            Self::Supplant => "Pop a symbol, then pop a program up to the topmost `;`, and redefine the symbol as that program.",
+            Self::Eval => "Pop a symbol and interpret it.",
+            Self::Semicolon => "Push `;`.",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Operation {
     Primitive(PrimOp),
     Program(Vec<Symbol>),
+    Native(Native),
     NoOp,
 }
 
@@ -39,6 +61,29 @@ impl Interpreter {
     pub fn supplant(&mut self, sym: Symbol, op: Operation) {
         self.map.insert(sym, op);
     }
+
+    /// The number of symbols in the programs that symbols are defined as.
+    pub fn size(&self) -> usize {
+        self.map
+            .values()
+            .map(|op| match op {
+                Operation::Program(program) => program.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// The symbols that aren't no-ops, in order, along with their operations.
+    pub fn operations(&self) -> Vec<(Symbol, &Operation)> {
+        let mut operations = self
+            .map
+            .iter()
+            .filter(|(_, op)| !matches!(op, Operation::NoOp))
+            .map(|(&sym, op)| (sym, op))
+            .collect::<Vec<_>>();
+        operations.sort_unstable_by_key(|&(sym, _)| sym);
+        operations
+    }
 }
 
 impl Default for Interpreter {
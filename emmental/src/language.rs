@@ -0,0 +1,315 @@
+//! Emmental as an `esolang_core` language, so it can be run by tools that support
+//! several languages.
+
+use std::io::{Read, Write};
+
+use esolang_core::{
+    io::InputOutputPair,
+    language::{Execution, Frame, Inspect, Language, Scope},
+    limits::Limits,
+};
+
+use crate::{
+    interpreter::{Interpreter, Operation},
+    state::State,
+    Error, Symbol,
+};
+
+/// Runs programs within the given limits.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Emmental {
+    pub limits: Limits,
+}
+
+impl Language for Emmental {
+    type Program = Vec<Symbol>;
+    type Error = Error;
+    type Execution<R: Read, W: Write> = Run<R, W>;
+
+    fn name(&self) -> &'static str {
+        "emmental"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["emm"]
+    }
+
+    fn parse(&self, source: &[u8]) -> Result<Self::Program, Error> {
+        Ok(source.to_vec())
+    }
+
+    fn start<R: Read, W: Write>(
+        &self,
+        program: Self::Program,
+        io: InputOutputPair<R, W>,
+    ) -> Self::Execution<R, W> {
+        let mut state = State::new(Interpreter::default(), io).with_limits(self.limits);
+        state.start(&program);
+        Run { state }
+    }
+}
+
+/// An Emmental program being run, where each step interprets a single symbol,
+/// including those of the programs that operations run.
+#[derive(Debug)]
+pub struct Run<R, W> {
+    state: State<InputOutputPair<R, W>>,
+}
+
+impl<R, W> Run<R, W> {
+    pub fn state(&self) -> &State<InputOutputPair<R, W>> {
+        &self.state
+    }
+}
+
+impl<R: Read, W: Write> Execution for Run<R, W> {
+    type Error = Error;
+    type IO = InputOutputPair<R, W>;
+
+    fn step(&mut self) -> Result<bool, Error> {
+        self.state.step()
+    }
+
+    fn into_io(self) -> Self::IO {
+        self.state.io
+    }
+}
+
+// Symbols are bytes, so they're shown as the characters with the same code points.
+impl<R, W> Inspect for Run<R, W> {
+    fn frames(&self) -> Vec<Frame> {
+        self.state
+            .call_stack()
+            .map(|(program, offset)| Frame {
+                offset,
+                len: program.len(),
+            })
+            .collect()
+    }
+
+    fn program(&self, depth: usize) -> Vec<char> {
+        self.state
+            .call_stack()
+            .nth(depth)
+            .map(|(program, _)| program.iter().map(|&sym| sym as char).collect())
+            .unwrap_or_default()
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        let describe = |(i, &sym): (usize, &Symbol)| (i.to_string(), describe_symbol(sym));
+
+        // Only operations that programs defined, as the primitive ones never move.
+        let redefinitions = self
+            .state
+            .interpreter()
+            .operations()
+            .into_iter()
+            .filter_map(|(sym, op)| {
+                let definition = match op {
+                    Operation::Program(program) => {
+                        format!(
+                            "{:?}",
+                            program.iter().map(|&s| s as char).collect::<String>()
+                        )
+                    }
+                    Operation::Native(native) => format!("native {}", native.name()),
+                    Operation::Primitive(_) | Operation::NoOp => return None,
+                };
+                Some((describe_symbol(sym), definition))
+            })
+            .collect();
+
+        vec![
+            Scope {
+                name: "Stack",
+                entries: self
+                    .state
+                    .stack()
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(describe)
+                    .collect(),
+            },
+            Scope {
+                name: "Queue",
+                entries: self
+                    .state
+                    .queue()
+                    .iter()
+                    .enumerate()
+                    .map(describe)
+                    .collect(),
+            },
+            Scope {
+                name: "Redefinitions",
+                entries: redefinitions,
+            },
+        ]
+    }
+}
+
+fn describe_symbol(sym: Symbol) -> String {
+    format!("{} {:?}", sym, sym as char)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::VecDeque, io, rc::Rc};
+
+    use super::*;
+
+    /// Input that has to wait for more once it runs out, rather than end.
+    struct Pending(Rc<RefCell<VecDeque<u8>>>);
+
+    impl Read for Pending {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.borrow_mut().read(buf)? {
+                0 if !buf.is_empty() => Err(io::ErrorKind::WouldBlock.into()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    #[test]
+    fn step_program() -> Result<(), Error> {
+        let program = Emmental::default().parse(b"#65#66..")?;
+        let mut execution =
+            Emmental::default().start(program, InputOutputPair::new(&b""[..], Vec::new()));
+
+        for _ in 0..3 {
+            assert!(execution.step()?);
+        }
+        assert_eq!(execution.state().stack.peek(), Some(&65));
+
+        execution.run()?;
+        assert!(!execution.step()?);
+        assert_eq!(execution.into_io().output, b"BA");
+        Ok(())
+    }
+
+    #[test]
+    fn end_of_input_is_eot() -> Result<(), Error> {
+        let program = Emmental::default().parse(b",.")?;
+        let mut execution =
+            Emmental::default().start(program, InputOutputPair::new(&b""[..], Vec::new()));
+
+        execution.run()?;
+        assert_eq!(execution.into_io().output, [4]);
+        Ok(())
+    }
+
+    #[test]
+    fn wait_for_input() -> Result<(), Error> {
+        let input = Rc::new(RefCell::new(VecDeque::new()));
+        // Evaluates `,`, which reads a symbol, then outputs it.
+        let program = Emmental::default().parse(b"#44?.")?;
+        let mut execution = Emmental::default().start(
+            program,
+            InputOutputPair::new(Pending(Rc::clone(&input)), Vec::new()),
+        );
+
+        for _ in 0..3 {
+            assert!(execution.step()?);
+        }
+        assert_eq!(execution.step(), Err(Error::NeedsInput));
+        assert_eq!(execution.step(), Err(Error::NeedsInput));
+        assert_eq!(execution.state().stack.peek(), Some(&44));
+
+        input.borrow_mut().push_back(b'a');
+        execution.run()?;
+        assert_eq!(execution.into_io().output, b"a");
+        Ok(())
+    }
+
+    #[test]
+    fn step_into_operation() -> Result<(), Error> {
+        // Redefines `a` as `#98.`, which outputs `b`, then evaluates it.
+        let program = Emmental::default().parse(b";#35#57#56#46#97!#97?")?;
+        let mut execution =
+            Emmental::default().start(program, InputOutputPair::new(&b""[..], Vec::new()));
+
+        let mut max_depth = 0;
+        while execution.step()? {
+            max_depth = max_depth.max(execution.state().call_stack().count());
+        }
+
+        assert_eq!(max_depth, 2);
+        assert_eq!(execution.into_io().output, b"b");
+        Ok(())
+    }
+
+    fn run_limited(source: &[u8], limits: Limits) -> Result<(), Error> {
+        let emmental = Emmental { limits };
+        let program = emmental.parse(source)?;
+        emmental
+            .start(program, InputOutputPair::new(&b""[..], Vec::new()))
+            .run()
+    }
+
+    #[test]
+    fn run_over_limits() {
+        let stack = Limits {
+            stack: Some(2),
+            ..Limits::default()
+        };
+        assert_eq!(
+            run_limited(b"###", stack),
+            Err(Error::StackLimit { limit: 2 })
+        );
+
+        let queue = Limits {
+            queue: Some(2),
+            ..Limits::default()
+        };
+        assert_eq!(
+            run_limited(b"#^^^", queue),
+            Err(Error::QueueLimit { limit: 2 })
+        );
+
+        // Redefining `a` as `#98.` holds on to four more symbols.
+        let symbols = Limits {
+            symbols: Some(23),
+            ..Limits::default()
+        };
+        assert_eq!(
+            run_limited(b";#35#57#56#46#97!###", symbols),
+            Err(Error::SymbolLimit { limit: 23 })
+        );
+        assert_eq!(
+            run_limited(b";#35#57#56#46#97!###", Limits::default()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn inspect_run() -> Result<(), Error> {
+        // Redefines `a` as `#98.`, then evaluates it, keeping a copy in the queue.
+        let program = Emmental::default().parse(b";#35#57#56#46#97!#97^?")?;
+        let mut execution =
+            Emmental::default().start(program, InputOutputPair::new(&b""[..], Vec::new()));
+        while execution.frames().len() < 2 {
+            assert!(execution.step()?);
+        }
+
+        assert_eq!(
+            execution.frames(),
+            [
+                Frame {
+                    offset: 22,
+                    len: 22
+                },
+                Frame { offset: 0, len: 4 }
+            ]
+        );
+        assert_eq!(execution.program(1), ['#', '9', '8', '.']);
+
+        let scopes = execution.scopes();
+        assert_eq!(scopes[1].entries, [("0".to_owned(), "97 'a'".to_owned())]);
+        assert_eq!(
+            scopes[2].entries,
+            [("97 'a'".to_owned(), "\"#98.\"".to_owned())]
+        );
+        Ok(())
+    }
+}
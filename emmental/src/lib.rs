@@ -1,37 +1,72 @@
-#![feature(never_type)]
+pub mod interpreter;
+pub mod language;
+pub mod native;
+pub mod state;
 
-mod interpreter;
-mod io;
-mod queue;
-mod stack;
-mod state;
-
-use std::slice;
+use std::{fmt::Display, slice};
 
+use esolang_core::io::{MemoryIO, SymbolIO};
 use interpreter::Interpreter;
-use io::{StringIO, SymbolIO};
 use state::State;
+use thiserror::Error;
 
 pub type Symbol = u8;
 pub type Program<'a> = slice::Iter<'a, Symbol>;
 
-pub fn run_with_io<IO: SymbolIO>(io: IO, program: &[Symbol]) -> Result<State<IO>, String> {
+/// Why a program failed. Emmental doesn't tell apart the ways a program can go
+/// wrong, but does tell those from going over one of the limits it was run with,
+/// and from having to wait for input.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("{0}")]
+    Failed(String),
+    /// The program read input that isn't there yet. The step that read it is
+    /// undone, so it can be taken again once there's input.
+    #[error("waiting for input")]
+    NeedsInput,
+    #[error("stack grew past its limit of {limit} symbols")]
+    StackLimit { limit: usize },
+    #[error("queue grew past its limit of {limit} symbols")]
+    QueueLimit { limit: usize },
+    #[error("held on to more than {limit} symbols")]
+    SymbolLimit { limit: usize },
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Self::Failed(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Self::Failed(message.to_owned())
+    }
+}
+
+pub fn run_with_io<IO>(io: IO, program: &[Symbol]) -> Result<State<IO>, Error>
+where
+    IO: SymbolIO<Symbol>,
+    IO::Error: Display,
+{
     let mut state = State::new(Interpreter::default(), io);
     state.run(&mut program.iter())?;
     Ok(state)
 }
 
-pub fn run_with_input(program: &[Symbol], input: &[Symbol]) -> Result<Vec<Symbol>, String> {
-    let state = run_with_io(StringIO::new(input), program)?;
+pub fn run_with_input(program: &[Symbol], input: &[Symbol]) -> Result<Vec<Symbol>, Error> {
+    let state = run_with_io(MemoryIO::new(input.iter().copied()), program)?;
     Ok(state.io.into_output())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use interpreter::Operation;
+    use native::Native;
 
     #[test]
-    fn run_with_input_hello_world() -> Result<(), String> {
+    fn run_with_input_hello_world() -> Result<(), Error> {
         let program = b"#0#10#33#100#108#114#111#119#32#44#111#108#108#101#72...............";
         let output = run_with_input(program, b"")?;
         assert_eq!(output, b"Hello, world!\n\0");
@@ -39,10 +74,54 @@ mod tests {
     }
 
     #[test]
-    fn run_with_input_hello_world_fancy() -> Result<(), String> {
-        let program = b";#58#126#63#36!;#46#36#!;#0#1!;#0#2!;#0#3!;#0#4!;#0#5!;#0#6!;#0#7!#0#33#100#108#114#111#119#32#44#111#108#108#101#72$";
+    fn run_with_input_hello_world_fancy() -> Result<(), Error> {
+        let program = b";#58#126#63#36!;#46#36#!;#0#1!;#0#2!;#0#3!;#0#4!;#0#5!;#0#6!;#0#7!#0#10#33#100#108#114#111#119#32#44#111#108#108#101#72$";
         let output = run_with_input(program, b"")?;
-        assert_eq!(output, b"Hello, world!\n\0");
+        assert_eq!(output, b"Hello, world!\n");
+        Ok(())
+    }
+
+    fn run_with_native(
+        program: &[Symbol],
+        sym: Symbol,
+        native: Native,
+    ) -> Result<Vec<Symbol>, Error> {
+        let mut interpreter = Interpreter::default();
+        interpreter.supplant(sym, Operation::Native(native));
+
+        let mut state = State::new(interpreter, MemoryIO::default());
+        state.run(&mut program.iter())?;
+        Ok(state.io.into_output())
+    }
+
+    fn multiply() -> Native {
+        Native::new("multiply", |machine| {
+            let rhs = machine.pop()?;
+            let lhs = machine.pop()?;
+            machine.push(lhs.wrapping_mul(rhs));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn run_native_operation() -> Result<(), Error> {
+        let output = run_with_native(b"#6#11*.", b'*', multiply())?;
+        assert_eq!(output, b"B");
+        Ok(())
+    }
+
+    #[test]
+    fn run_native_operation_with_eval() -> Result<(), Error> {
+        let output = run_with_native(b"#6#11#42?.", b'*', multiply())?;
+        assert_eq!(output, b"B");
+        Ok(())
+    }
+
+    #[test]
+    fn native_operation_can_be_supplanted() -> Result<(), Error> {
+        // Redefine `*` as a program that outputs the top of the stack.
+        let output = run_with_native(b"#65;#46#42!*", b'*', multiply())?;
+        assert_eq!(output, b"A");
         Ok(())
     }
 
@@ -0,0 +1,68 @@
+use std::fmt::Display;
+
+use esolang_core::{io::SymbolIO, native};
+
+use crate::{language::Emmental, state::State, Symbol};
+
+/// A named Rust closure that can be bound to a symbol like any primitive operation,
+/// e.g. to try out extensions to the language.
+pub type Native = native::Native<Emmental>;
+
+impl native::Extensible for Emmental {
+    type Machine<'a> = dyn Machine + 'a;
+    type Error = String;
+}
+
+/// What a native operation can do to the state, independent of its type of IO.
+/// Reading reads EOT once the input has run out.
+pub trait Machine: native::Machine<Symbol = Symbol, Error = String> {
+    fn pop(&mut self) -> Result<Symbol, String>;
+    fn push(&mut self, sym: Symbol);
+    fn peek(&self) -> Result<Symbol, String>;
+    fn enqueue(&mut self, sym: Symbol);
+    fn dequeue(&mut self) -> Result<Symbol, String>;
+}
+
+impl<IO> Machine for State<IO>
+where
+    IO: SymbolIO<Symbol>,
+    IO::Error: Display,
+{
+    fn pop(&mut self) -> Result<Symbol, String> {
+        State::pop(self)
+    }
+
+    fn push(&mut self, sym: Symbol) {
+        self.stack.push(sym)
+    }
+
+    fn peek(&self) -> Result<Symbol, String> {
+        State::peek(self)
+    }
+
+    fn enqueue(&mut self, sym: Symbol) {
+        self.queue.enqueue(sym)
+    }
+
+    fn dequeue(&mut self) -> Result<Symbol, String> {
+        State::dequeue(self)
+    }
+}
+
+impl<IO> native::Machine for State<IO>
+where
+    IO: SymbolIO<Symbol>,
+    IO::Error: Display,
+{
+    type Symbol = Symbol;
+    type Error = String;
+
+    // Natives can't be undone, so they can't wait for input.
+    fn read_symbol(&mut self) -> Result<Symbol, String> {
+        State::read_symbol(self).map_err(|e| e.to_string())
+    }
+
+    fn write_symbol(&mut self, sym: Symbol) -> Result<(), String> {
+        State::write_symbol(self, sym)
+    }
+}
@@ -1,3 +0,0 @@
-use std::collections::VecDeque;
-
-pub type Queue<T> = VecDeque<T>;
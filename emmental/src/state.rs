@@ -1,102 +1,309 @@
+use std::fmt::Display;
+
+use esolang_core::{io::SymbolIO, limits::Limits, queue::Queue, stack::Stack};
+
 use crate::{
     interpreter::{Interpreter, Operation, PrimOp},
-    io::SymbolIO,
-    queue::Queue,
-    stack::Stack,
-    Program, Symbol,
+    Error, Program, Symbol,
 };
 
+/// What the input symbol operation gives once the input has run out.
+const EOT: Symbol = 4;
+
 #[derive(Debug, Clone)]
 pub struct State<IO> {
-    stack: Stack<Symbol>,
-    queue: Queue<Symbol>,
+    pub(crate) stack: Stack<Symbol>,
+    pub(crate) queue: Queue<Symbol>,
     interpreter: Interpreter,
+    frames: Vec<Frame>,
+    /// The number of symbols in the programs of `frames`.
+    frame_symbols: usize,
+    limits: Limits,
     pub io: IO,
 }
 
-impl<IO: SymbolIO> State<IO> {
+/// A program that is being run. Programs of operations get a frame of their own,
+/// rather than being run recursively, so they can be stepped through.
+#[derive(Debug, Clone)]
+struct Frame {
+    program: Vec<Symbol>,
+    /// The offset of the next symbol to interpret.
+    offset: usize,
+}
+
+impl<IO> State<IO> {
+    pub fn stack(&self) -> &Stack<Symbol> {
+        &self.stack
+    }
+
+    pub fn queue(&self) -> &Queue<Symbol> {
+        &self.queue
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// The number of symbols held on to: those on the stack, in the queue, in the
+    /// programs being run and in the programs of redefined symbols.
+    pub fn symbols(&self) -> usize {
+        self.stack.len() + self.queue.len() + self.frame_symbols + self.interpreter.size()
+    }
+
+    /// The programs that are being run, outermost first, along with the offset of
+    /// the next symbol to interpret in each.
+    pub fn call_stack(&self) -> impl Iterator<Item = (&[Symbol], usize)> {
+        self.frames
+            .iter()
+            .map(|frame| (frame.program.as_slice(), frame.offset))
+    }
+}
+
+impl<IO> State<IO>
+where
+    IO: SymbolIO<Symbol>,
+    IO::Error: Display,
+{
     pub fn new(interpreter: Interpreter, io: IO) -> Self {
         Self {
             stack: Stack::new(),
             queue: Queue::new(),
             interpreter,
+            frames: Vec::new(),
+            frame_symbols: 0,
+            limits: Limits::unlimited(),
             io,
         }
     }
 
-    pub fn run(&mut self, program: &mut Program) -> Result<(), String> {
-        for &sym in program.next() {
-            self.interpret_symbol(sym)?;
+    /// Fail as soon as the program goes over one of `limits`. Emmental has no
+    /// interpreter towers, so there's no limit on those.
+    pub fn with_limits(self, limits: Limits) -> Self {
+        Self { limits, ..self }
+    }
+
+    pub fn run(&mut self, program: &mut Program) -> Result<(), Error> {
+        let base = self.frames.len();
+        self.push_frame(program.copied().collect());
+        self.run_frames(base)
+    }
+
+    /// Start running `program`, without interpreting any of it yet. Use `step` to
+    /// do that.
+    pub fn start(&mut self, program: &[Symbol]) {
+        self.push_frame(program.to_vec());
+    }
+
+    /// Interpret the next symbol of the programs that have been started, returning
+    /// false if they're all done.
+    ///
+    /// After an error, all programs that have been started are abandoned, except
+    /// when the error is `NeedsInput`, which leaves the state as it was before the
+    /// step.
+    pub fn step(&mut self) -> Result<bool, Error> {
+        let result = self.step_above(0);
+        if matches!(result, Err(ref e) if *e != Error::NeedsInput) {
+            self.drop_frames(0);
+        }
+
+        result
+    }
+
+    pub fn interpret_symbol(&mut self, sym: Symbol) -> Result<(), Error> {
+        let base = self.frames.len();
+        self.dispatch(sym)?;
+        self.run_frames(base)
+    }
+
+    // Run until all frames above `base` are done, dropping them after an error.
+    fn run_frames(&mut self, base: usize) -> Result<(), Error> {
+        let mut result = Ok(true);
+        while let Ok(true) = result {
+            result = self.step_above(base);
+        }
+
+        if result.is_err() {
+            self.drop_frames(base);
+        }
+        result.map(|_| ())
+    }
+
+    // Interpret the next symbol of the frames above `base`, returning false if
+    // they're all done. Frames that are done along the way are dropped.
+    fn step_above(&mut self, base: usize) -> Result<bool, Error> {
+        while self.frames.len() > base {
+            let frame = match self.frames.last_mut() {
+                Some(frame) => frame,
+                None => break,
+            };
+
+            match frame.program.get(frame.offset) {
+                Some(&sym) => {
+                    frame.offset += 1;
+                    // Operations read before they change anything else, and eval
+                    // puts back the symbol it popped, so only the step itself needs
+                    // undoing if there's no input yet.
+                    if let Err(e) = self.dispatch(sym) {
+                        if e == Error::NeedsInput {
+                            if let Some(frame) = self.frames.last_mut() {
+                                frame.offset -= 1;
+                            }
+                        }
+                        return Err(e);
+                    }
+                    self.check_limits()?;
+                    return Ok(true);
+                }
+                None => self.drop_frames(self.frames.len() - 1),
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn push_frame(&mut self, program: Vec<Symbol>) {
+        self.frame_symbols += program.len();
+        self.frames.push(Frame { program, offset: 0 });
+    }
+
+    fn drop_frames(&mut self, base: usize) {
+        for frame in self.frames.drain(base..) {
+            self.frame_symbols -= frame.program.len();
+        }
+    }
+
+    fn check_limits(&self) -> Result<(), Error> {
+        if let Some(limit) = Limits::exceeded(self.limits.stack, self.stack.len()) {
+            return Err(Error::StackLimit { limit });
+        }
+        if let Some(limit) = Limits::exceeded(self.limits.queue, self.queue.len()) {
+            return Err(Error::QueueLimit { limit });
+        }
+        // Working out the number of symbols means going through the interpreter.
+        if let Some(limit) = self.limits.symbols {
+            if let Some(limit) = Limits::exceeded(Some(limit), self.symbols()) {
+                return Err(Error::SymbolLimit { limit });
+            }
         }
 
         Ok(())
     }
 
-    pub fn interpret_symbol(&mut self, sym: Symbol) -> Result<(), String> {
+    // Interpret `sym`, but start the programs of operations instead of running them.
+    fn dispatch(&mut self, sym: Symbol) -> Result<(), Error> {
         let operation = self.interpreter.lookup(sym).clone();
 
         match operation {
+            Operation::Primitive(PrimOp::Eval) => {
+                let sym = self.pop()?;
+                let result = self.dispatch(sym);
+                if result == Err(Error::NeedsInput) {
+                    self.stack.push(sym);
+                }
+                result
+            }
             Operation::Primitive(primop) => self.step_primop(primop),
-            Operation::Program(program) => self.run(&mut program.iter()),
+            Operation::Program(program) => {
+                self.push_frame(program);
+                Ok(())
+            }
+            Operation::Native(native) => Ok(native.call(self)?),
             Operation::NoOp => Ok(()),
         }
     }
 
-    pub fn step_primop(&mut self, primop: PrimOp) -> Result<(), String> {
-        Ok(match primop {
+    pub fn step_primop(&mut self, primop: PrimOp) -> Result<(), Error> {
+        match primop {
             PrimOp::Nul => self.stack.push(0),
             PrimOp::Semicolon => self.stack.push(b';'),
             PrimOp::Digit(d) => {
-                let sym = self.stack.pop()?;
+                let sym = self.pop()?;
                 self.stack.push(sym.wrapping_mul(10).wrapping_add(d));
             }
             PrimOp::Add => {
-                let rhs = self.stack.pop()?;
-                let lhs = self.stack.pop()?;
+                let rhs = self.pop()?;
+                let lhs = self.pop()?;
                 self.stack.push(lhs.wrapping_add(rhs))
             }
             PrimOp::Sub => {
-                let rhs = self.stack.pop()?;
-                let lhs = self.stack.pop()?;
+                let rhs = self.pop()?;
+                let lhs = self.pop()?;
                 self.stack.push(lhs.wrapping_sub(rhs))
             }
             PrimOp::Log2 => {
-                let sym = self.stack.pop()?;
+                let sym = self.pop()?;
                 self.stack.push(match sym {
                     0 => 8,
                     n => (n as f64).log2().floor() as u8,
                 })
             }
             PrimOp::Output => {
-                let sym = self.stack.pop()?;
-                self.io.write_symbol(sym).map_err(|e| e.to_string())?
+                let sym = self.pop()?;
+                self.write_symbol(sym)?
             }
             PrimOp::Input => {
-                let sym = self.io.read_symbol().map_err(|e| e.to_string())?;
+                let sym = self.read_symbol()?;
                 self.stack.push(sym)
             }
             PrimOp::Enqueue => {
-                let sym = self.stack.peek()?;
-                self.queue.push_back(*sym)
+                let sym = self.peek()?;
+                self.queue.enqueue(sym)
             }
             PrimOp::Dequeue => {
-                let sym = self.queue.pop_front().ok_or("queue is empty")?;
+                let sym = self.dequeue()?;
                 self.stack.push(sym)
             }
             PrimOp::Duplicate => {
-                let sym = self.stack.pop()?;
+                let sym = self.pop()?;
                 self.stack.push(sym);
                 self.stack.push(sym)
             }
             PrimOp::Supplant => {
-                let sym = self.stack.pop()?;
-                let program = self.stack.pop_string(b';')?;
+                let sym = self.pop()?;
+                let program = self
+                    .stack
+                    .pop_until(&b';')
+                    .ok_or("prematurely terminated string")?;
                 self.interpreter.supplant(sym, Operation::Program(program))
             }
             PrimOp::Eval => {
-                let sym = self.stack.pop()?;
+                let sym = self.pop()?;
                 self.interpret_symbol(sym)?
             }
-        })
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn pop(&mut self) -> Result<Symbol, String> {
+        self.stack.pop().ok_or_else(|| "stack is empty".into())
+    }
+
+    pub(crate) fn peek(&self) -> Result<Symbol, String> {
+        self.stack
+            .peek()
+            .copied()
+            .ok_or_else(|| "stack is empty".into())
+    }
+
+    pub(crate) fn dequeue(&mut self) -> Result<Symbol, String> {
+        self.queue.dequeue().ok_or_else(|| "queue is empty".into())
+    }
+
+    /// Read a symbol, or EOT if the input has run out.
+    pub(crate) fn read_symbol(&mut self) -> Result<Symbol, Error> {
+        match self.io.read_symbol() {
+            Ok(sym) => Ok(sym.unwrap_or(EOT)),
+            Err(e) if IO::is_pending(&e) => Err(Error::NeedsInput),
+            Err(e) => Err(e.to_string().into()),
+        }
+    }
+
+    pub(crate) fn write_symbol(&mut self, sym: Symbol) -> Result<(), String> {
+        self.io.write_symbol(sym).map_err(|e| e.to_string())
     }
 }
@@ -0,0 +1,97 @@
+//! Reading and writing messages framed the way the Language Server Protocol and the
+//! Debug Adapter Protocol do it: a `Content-Length` header, an empty line, and the
+//! body.
+
+use std::io::{self, BufRead, Read, Write};
+
+/// The longest body `read_frame` accepts, so that a bad header can't make it try
+/// to allocate more than that.
+pub const MAX_FRAME_LENGTH: usize = 64 << 20;
+
+/// Read the body of the next message, or `None` if the input has ended.
+pub fn read_frame(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        // Other headers, i.e. `Content-Type`, only ever have one value in practice.
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    if length > MAX_FRAME_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Content-Length of {} is too long", length),
+        ));
+    }
+
+    // Only grow the body as bytes come in, rather than trusting the header.
+    let mut body = Vec::new();
+    reader.by_ref().take(length as u64).read_to_end(&mut body)?;
+    if body.len() < length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some(body))
+}
+
+pub fn write_frame(writer: &mut impl Write, body: &[u8]) -> io::Result<()> {
+    write!(writer, "Content-Length: {}\r\n\r\n", body.len())?;
+    writer.write_all(body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, "{\"é\": 1}".as_bytes()).unwrap();
+        write_frame(&mut buffer, b"").unwrap();
+
+        let mut reader = &buffer[..];
+        assert_eq!(
+            read_frame(&mut reader).unwrap(),
+            Some("{\"é\": 1}".as_bytes().to_vec())
+        );
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(Vec::new()));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn missing_length() {
+        let mut reader = &b"Content-Type: text/plain\r\n\r\n{}"[..];
+        assert!(read_frame(&mut reader).is_err());
+    }
+
+    #[test]
+    fn length_too_long() {
+        let mut reader = &b"Content-Length: 18446744073709551615\r\n\r\n{}"[..];
+        let error = read_frame(&mut reader).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn body_too_short() {
+        let mut reader = &b"Content-Length: 3\r\n\r\n{}"[..];
+        let error = read_frame(&mut reader).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
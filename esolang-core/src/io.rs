@@ -0,0 +1,218 @@
+//! Reading and writing one symbol at a time, whatever a language considers a symbol
+//! to be, e.g. a byte or a `char`.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    convert::Infallible,
+    io::{self, Read, Stdin, Stdout, Write},
+    iter::FromIterator,
+    rc::Rc,
+};
+
+pub trait SymbolIO<S> {
+    type Error;
+
+    /// Read the next symbol, or `None` if the end of the input has been reached.
+    fn read_symbol(&mut self) -> Result<Option<S>, Self::Error>;
+    fn write_symbol(&mut self, sym: S) -> Result<(), Self::Error>;
+
+    /// Whether `error` only means that there's no input yet, so reading can be
+    /// tried again once there is, rather than that reading failed.
+    fn is_pending(_error: &Self::Error) -> bool {
+        false
+    }
+}
+
+impl<S, T: SymbolIO<S> + ?Sized> SymbolIO<S> for &mut T {
+    type Error = T::Error;
+
+    fn read_symbol(&mut self) -> Result<Option<S>, Self::Error> {
+        (**self).read_symbol()
+    }
+
+    fn write_symbol(&mut self, sym: S) -> Result<(), Self::Error> {
+        (**self).write_symbol(sym)
+    }
+
+    fn is_pending(error: &Self::Error) -> bool {
+        T::is_pending(error)
+    }
+}
+
+/// Combines a separate reader and writer into something that implements both `Read`
+/// and `Write`, e.g. for standard input and output. As symbol IO, it reads and
+/// writes bytes.
+#[derive(Debug)]
+pub struct InputOutputPair<I, O> {
+    pub input: I,
+    pub output: O,
+}
+
+impl<I, O> InputOutputPair<I, O> {
+    pub fn new(input: I, output: O) -> Self {
+        Self { input, output }
+    }
+}
+
+impl InputOutputPair<Stdin, Stdout> {
+    pub fn standard() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+}
+
+impl<I: Read, O> Read for InputOutputPair<I, O> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl<I, O: Write> Write for InputOutputPair<I, O> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+impl<I: Read, O: Write> SymbolIO<u8> for InputOutputPair<I, O> {
+    type Error = io::Error;
+
+    fn read_symbol(&mut self) -> io::Result<Option<u8>> {
+        let mut buf = [0];
+        loop {
+            match self.input.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buf[0])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write_symbol(&mut self, sym: u8) -> io::Result<()> {
+        self.output.write_all(&[sym])
+    }
+
+    /// Input that would block, e.g. because it's non-blocking, hasn't arrived yet.
+    fn is_pending(error: &io::Error) -> bool {
+        error.kind() == io::ErrorKind::WouldBlock
+    }
+}
+
+/// Symbol IO that reads from and writes to memory, e.g. for tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryIO<S> {
+    input: VecDeque<S>,
+    output: Vec<S>,
+}
+
+impl<S> MemoryIO<S> {
+    pub fn new(input: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            input: input.into_iter().collect(),
+            output: Vec::new(),
+        }
+    }
+
+    pub fn output(&self) -> &[S] {
+        &self.output
+    }
+
+    pub fn into_output(self) -> Vec<S> {
+        self.output
+    }
+
+    /// Collect the output into e.g. a `String`.
+    pub fn collect_output<C: FromIterator<S>>(self) -> C {
+        self.output.into_iter().collect()
+    }
+}
+
+impl<S> Default for MemoryIO<S> {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<S> SymbolIO<S> for MemoryIO<S> {
+    type Error = Infallible;
+
+    fn read_symbol(&mut self) -> Result<Option<S>, Self::Error> {
+        Ok(self.input.pop_front())
+    }
+
+    fn write_symbol(&mut self, sym: S) -> Result<(), Self::Error> {
+        self.output.push(sym);
+        Ok(())
+    }
+}
+
+/// Output that can be looked at or taken while a program is still writing to it,
+/// through a clone kept by whoever is watching.
+#[derive(Debug, Clone, Default)]
+pub struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl SharedOutput {
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+
+    /// The output written since it was last taken.
+    pub fn take(&self) -> Vec<u8> {
+        self.0.borrow_mut().split_off(0)
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_output_pair_bytes() {
+        let mut io = InputOutputPair::new(&b"ab"[..], Vec::new());
+
+        while let Some(sym) = io.read_symbol().unwrap() {
+            io.write_symbol(sym.to_ascii_uppercase()).unwrap();
+        }
+
+        assert_eq!(io.output, b"AB");
+    }
+
+    #[test]
+    fn memory_io_chars() {
+        let mut io = MemoryIO::new("a€".chars());
+
+        let sym = io.read_symbol().unwrap().unwrap();
+        io.write_symbol(sym).unwrap();
+        let sym = io.read_symbol().unwrap().unwrap();
+        io.write_symbol(sym).unwrap();
+
+        assert_eq!(io.read_symbol(), Ok(None));
+        assert_eq!(io.collect_output::<String>(), "a€");
+    }
+
+    #[test]
+    fn shared_output_clones() {
+        let output = SharedOutput::default();
+        output.clone().write_all(b"ab").unwrap();
+        assert_eq!(output.contents(), b"ab");
+
+        assert_eq!(output.take(), b"ab");
+        output.clone().write_all(b"c").unwrap();
+        assert_eq!(output.take(), b"c");
+    }
+}
@@ -0,0 +1,142 @@
+//! A common interface to the languages in this workspace, so tools can work with any
+//! of them without knowing which one they're dealing with.
+
+use std::{
+    fmt,
+    io::{Read, Write},
+};
+
+use crate::io::InputOutputPair;
+
+pub trait Language {
+    /// A program that has been parsed and is ready to run.
+    type Program;
+    type Error: fmt::Display;
+    type Execution<R: Read, W: Write>: Execution<Error = Self::Error, IO = InputOutputPair<R, W>>
+        + Inspect;
+
+    fn name(&self) -> &'static str;
+
+    /// The file extensions of programs in the language, without the leading dot.
+    fn extensions(&self) -> &'static [&'static str];
+
+    fn parse(&self, source: &[u8]) -> Result<Self::Program, Self::Error>;
+
+    /// Prepare to run `program`, reading from and writing to `io`. Nothing is run
+    /// until the execution is stepped.
+    fn start<R: Read, W: Write>(
+        &self,
+        program: Self::Program,
+        io: InputOutputPair<R, W>,
+    ) -> Self::Execution<R, W>;
+
+    /// Describe `error` for showing to a user, ending in a newline.
+    fn report(&self, error: &Self::Error) -> String {
+        format!("error: {}\n", error)
+    }
+}
+
+/// A program in the middle of being run.
+pub trait Execution {
+    type Error;
+    type IO;
+
+    /// Run the program a little further, returning false once it's done. How far a
+    /// step goes is up to the language, but it should be small.
+    fn step(&mut self) -> Result<bool, Self::Error>;
+
+    /// Run the rest of the program.
+    fn run(&mut self) -> Result<(), Self::Error> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    fn into_io(self) -> Self::IO;
+}
+
+/// A look inside a program being run, for tools that show what's going on, like
+/// debuggers.
+pub trait Inspect {
+    /// The programs being run, outermost first. The outermost one is always the
+    /// program that was started, until it's dropped, even while it's finished and
+    /// only waiting for the operations it ran to finish; the others are run by
+    /// operations.
+    fn frames(&self) -> Vec<Frame>;
+
+    /// Like `frames`, but leaving out the innermost frames that are finished, as
+    /// they're only waiting to be dropped. Whatever is run next is part of the
+    /// innermost frame left, if there is one.
+    fn active_frames(&self) -> Vec<Frame> {
+        let mut frames = self.frames();
+        while frames.last().is_some_and(Frame::is_finished) {
+            frames.pop();
+        }
+        frames
+    }
+
+    /// The program of the frame at `depth`, with a character for each symbol.
+    fn program(&self, depth: usize) -> Vec<char>;
+
+    /// Everything else worth looking at, like the stack, in the order it's best
+    /// shown in.
+    fn scopes(&self) -> Vec<Scope>;
+}
+
+/// A program being run, whatever its language, for tools that pick the language at
+/// runtime. Errors are reported as they should be shown to the user.
+pub trait AnyExecution {
+    /// Run the program a little further, returning false once it's done.
+    fn step(&mut self) -> Result<bool, String>;
+
+    fn inspect(&self) -> &dyn Inspect;
+}
+
+/// An execution along with its language, to report its errors with.
+pub struct Started<L: Language, R: Read, W: Write> {
+    language: L,
+    execution: L::Execution<R, W>,
+}
+
+impl<L: Language, R: Read, W: Write> Started<L, R, W> {
+    /// Parse `source` and start running it, or report why it can't be parsed.
+    pub fn new(language: L, source: &[u8], io: InputOutputPair<R, W>) -> Result<Self, String> {
+        let program = language.parse(source).map_err(|e| language.report(&e))?;
+        let execution = language.start(program, io);
+        Ok(Self {
+            language,
+            execution,
+        })
+    }
+}
+
+impl<L: Language, R: Read, W: Write> AnyExecution for Started<L, R, W> {
+    fn step(&mut self) -> Result<bool, String> {
+        self.execution.step().map_err(|e| self.language.report(&e))
+    }
+
+    fn inspect(&self) -> &dyn Inspect {
+        &self.execution
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// The offset of the next symbol to interpret.
+    pub offset: usize,
+    /// The length of the frame's program.
+    pub len: usize,
+}
+
+impl Frame {
+    pub fn is_finished(&self) -> bool {
+        self.offset >= self.len
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scope {
+    pub name: &'static str,
+    /// Names and descriptions of values, e.g. positions on a stack and the elements
+    /// there.
+    pub entries: Vec<(String, String)>,
+}
@@ -0,0 +1,9 @@
+//! Building blocks shared by the interpreters in this workspace.
+
+pub mod framing;
+pub mod io;
+pub mod language;
+pub mod limits;
+pub mod native;
+pub mod queue;
+pub mod stack;
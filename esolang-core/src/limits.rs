@@ -0,0 +1,42 @@
+/// Limits on how much memory a program may take up. `None` means there's no limit.
+///
+/// There are no limits by default, so running untrusted programs safely means
+/// setting these, along with a budget on how long the programs may run.
+///
+/// Languages check these after every symbol they interpret, and ignore the ones
+/// that don't apply to them. Going over a limit is an error of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The number of elements on the stack.
+    pub stack: Option<usize>,
+    /// The number of symbols in the queue.
+    pub queue: Option<usize>,
+    /// The number of interpreters in the tower of any interpreter, counting itself.
+    pub tower: Option<usize>,
+    /// The number of symbols held on to in total. What counts is up to the
+    /// language, but at least the stack, the queue and the programs being run do.
+    pub symbols: Option<usize>,
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Whether `amount` goes over `limit`, giving the limit if it does.
+    pub fn exceeded(limit: Option<usize>, amount: usize) -> Option<usize> {
+        limit.filter(|&limit| amount > limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exceeded() {
+        assert_eq!(Limits::exceeded(None, usize::MAX), None);
+        assert_eq!(Limits::exceeded(Some(3), 3), None);
+        assert_eq!(Limits::exceeded(Some(3), 4), Some(3));
+    }
+}
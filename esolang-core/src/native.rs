@@ -0,0 +1,94 @@
+//! Operations implemented in Rust, so a host program can extend a language with
+//! things that can't be expressed in the language itself.
+//!
+//! Each language has a `Machine` trait of its own for the parts of its state that
+//! native operations can use, which extends the one here with its stack and the
+//! like, and implements `Extensible` to tie it to its native operations.
+
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+/// What every language's native operations can do, independent of the type of
+/// the state's IO.
+pub trait Machine {
+    type Symbol;
+    type Error;
+
+    /// Read a symbol from the input.
+    fn read_symbol(&mut self) -> Result<Self::Symbol, Self::Error>;
+    /// Write a symbol to the output.
+    fn write_symbol(&mut self, sym: Self::Symbol) -> Result<(), Self::Error>;
+}
+
+/// A language whose operations can be implemented in Rust.
+pub trait Extensible {
+    /// What native operations work on, usually `dyn Machine + 'a` for the
+    /// language's own `Machine` trait.
+    type Machine<'a>: ?Sized + 'a;
+    type Error;
+}
+
+type Function<L> = dyn for<'a> Fn(&mut <L as Extensible>::Machine<'a>) -> Result<(), <L as Extensible>::Error>
+    + Send
+    + Sync;
+
+/// A named Rust closure that can be used as an operation in the language `L`.
+pub struct Native<L: Extensible> {
+    name: String,
+    function: Arc<Function<L>>,
+}
+
+impl<L: Extensible> Native<L> {
+    pub fn new(
+        name: impl Into<String>,
+        function: impl for<'a> Fn(&mut L::Machine<'a>) -> Result<(), L::Error> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            function: Arc::new(function),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn call(&self, machine: &mut L::Machine<'_>) -> Result<(), L::Error> {
+        (self.function)(machine)
+    }
+}
+
+// Deriving these would require the language to implement them as well.
+impl<L: Extensible> Clone for Native<L> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            function: Arc::clone(&self.function),
+        }
+    }
+}
+
+impl<L: Extensible> fmt::Debug for Native<L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Native").field(&self.name).finish()
+    }
+}
+
+// Natives are only equal to clones of themselves, since there's no way to compare
+// closures. Hashing just the name keeps hashes the same between runs.
+impl<L: Extensible> PartialEq for Native<L> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.function, &other.function) && self.name == other.name
+    }
+}
+
+impl<L: Extensible> Eq for Native<L> {}
+
+impl<L: Extensible> Hash for Native<L> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state)
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Queue<T> {
+    storage: VecDeque<T>,
+}
+
+impl<T> Queue<T> {
+    pub fn new() -> Self {
+        Queue {
+            storage: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    pub fn enqueue(&mut self, value: T) {
+        self.storage.push_back(value)
+    }
+
+    pub fn dequeue(&mut self) -> Option<T> {
+        self.storage.pop_front()
+    }
+
+    /// The element that will be dequeued next.
+    pub fn peek(&self) -> Option<&T> {
+        self.storage.front()
+    }
+
+    /// Iterate over the elements of the queue, from the front to the back.
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, T> {
+        self.storage.iter()
+    }
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
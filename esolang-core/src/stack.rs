@@ -0,0 +1,195 @@
+use std::iter;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stack<T> {
+    storage: Vec<T>,
+}
+
+impl<T> Stack<T> {
+    pub fn new() -> Self {
+        Stack {
+            storage: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.storage.pop()
+    }
+
+    pub fn pop_while<'a, P>(&'a mut self, mut pred: P) -> impl Iterator<Item = T> + 'a
+    where
+        P: FnMut(&T) -> bool + 'a,
+    {
+        iter::from_fn(move || {
+            let x = self.pop()?;
+            if pred(&x) {
+                Some(x)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Pop everything up to and including the topmost `terminator`, returning what
+    /// was above it, from the bottom up. If there's no terminator, the stack is left
+    /// as it is.
+    pub fn pop_until(&mut self, terminator: &T) -> Option<Vec<T>>
+    where
+        T: PartialEq,
+    {
+        let start = self.storage.iter().rposition(|x| x == terminator)?;
+        let string = self.storage.split_off(start + 1);
+        self.storage.pop();
+        Some(string)
+    }
+
+    /// Pop a string enclosed in `open` and `close` off the top of the stack, returning
+    /// what's inside the delimiters, from the bottom up. Delimiters may be nested in
+    /// the string, as long as they're balanced. If the top of the stack isn't a
+    /// complete string, the stack is left as it is.
+    pub fn pop_delimited(&mut self, open: &T, close: &T) -> Option<Vec<T>>
+    where
+        T: PartialEq,
+    {
+        let (last, rest) = self.storage.split_last()?;
+        if last != close {
+            return None;
+        }
+
+        let mut nesting = 0usize;
+        let start = rest.iter().rposition(|x| {
+            if x == close {
+                nesting += 1;
+            } else if x == open {
+                if nesting == 0 {
+                    return true;
+                }
+                nesting -= 1;
+            }
+            false
+        })?;
+
+        let mut string = self.storage.split_off(start + 1);
+        string.pop();
+        self.storage.pop();
+        Some(string)
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.storage.push(value)
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.storage.last()
+    }
+
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        self.storage.last_mut()
+    }
+
+    /// Iterate over the elements of the stack, from the bottom to the top.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.storage.iter()
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Extend<T> for Stack<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.storage.extend(iter)
+    }
+}
+
+impl<T> iter::FromIterator<T> for Stack<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Stack {
+            storage: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pop_while_empty() {
+        let mut stack = Stack::<u32>::new();
+
+        let mut iter = stack.pop_while(|_| true);
+
+        assert_eq!(iter.next(), None)
+    }
+
+    #[test]
+    fn pop_while_const_false() {
+        let mut stack = Stack {
+            storage: vec![1u32, 2, 3],
+        };
+
+        let mut iter = stack.pop_while(|_| false);
+
+        assert_eq!(iter.next(), None)
+    }
+
+    #[test]
+    fn pop_while() {
+        let mut stack = Stack {
+            storage: vec![1u32, 2, 3],
+        };
+
+        let iter = stack.pop_while(|&n| n > 1u32);
+
+        assert_eq!(iter.collect::<Vec<_>>(), vec![3, 2])
+    }
+
+    #[test]
+    fn pop_until() {
+        let mut stack = "a;bc".chars().collect::<Stack<_>>();
+
+        assert_eq!(stack.pop_until(&';'), Some(vec!['b', 'c']));
+        assert_eq!(stack.iter().collect::<String>(), "a");
+    }
+
+    #[test]
+    fn pop_until_missing_terminator() {
+        let mut stack = "abc".chars().collect::<Stack<_>>();
+
+        assert_eq!(stack.pop_until(&';'), None);
+        assert_eq!(stack.len(), 3);
+    }
+
+    #[test]
+    fn pop_delimited_nested() {
+        let mut stack = "a]b[c[d]e]".chars().collect::<Stack<_>>();
+
+        assert_eq!(
+            stack.pop_delimited(&'[', &']'),
+            Some("c[d]e".chars().collect())
+        );
+        assert_eq!(stack.iter().collect::<String>(), "a]b");
+    }
+
+    #[test]
+    fn pop_delimited_incomplete() {
+        for string in ["", "ab", "[a", "a]", "a[b]]"].iter() {
+            let mut stack = string.chars().collect::<Stack<_>>();
+
+            assert_eq!(stack.pop_delimited(&'[', &']'), None);
+            assert_eq!(stack.iter().collect::<String>(), *string);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Programs being debugged, whatever their language.
+
+use std::io::Cursor;
+
+use emmental::language::Emmental;
+use esolang_core::{
+    io::{InputOutputPair, SharedOutput},
+    language::{AnyExecution, Language, Started},
+};
+use mascarpone::language::Mascarpone;
+
+/// How long descriptions of programs in stack frames may get.
+const MAX_PROGRAM_LENGTH: usize = 16;
+
+/// A loaded program, ready to be stepped through.
+pub struct Program {
+    pub debuggee: Box<dyn AnyExecution>,
+    /// The line and column of each of the program's symbols, counting from zero.
+    pub positions: Vec<(usize, usize)>,
+}
+
+impl Program {
+    /// A name for the frame at `depth`, for stack traces.
+    pub fn frame_name(&self, depth: usize) -> String {
+        if depth == 0 {
+            return "program".to_owned();
+        }
+
+        let program = self.debuggee.inspect().program(depth);
+        let text = program.iter().take(MAX_PROGRAM_LENGTH).collect::<String>();
+        let ellipsis = if program.len() > MAX_PROGRAM_LENGTH {
+            "..."
+        } else {
+            ""
+        };
+        format!("operation {:?}{}", text, ellipsis)
+    }
+}
+
+/// Load `source` as a program in `language`, or else in the language with `path`'s
+/// extension.
+pub fn load(
+    path: &str,
+    language: Option<&str>,
+    source: &[u8],
+    input: &[u8],
+    output: SharedOutput,
+) -> Result<Program, String> {
+    let extension = path.rsplit_once('.').map(|(_, ext)| ext);
+    let is = |name: &str, extensions: &[&str]| match language {
+        Some(language) => language == name,
+        None => extension.is_some_and(|e| extensions.contains(&e)),
+    };
+    let io = InputOutputPair::new(Cursor::new(input.to_vec()), output);
+
+    if is(Emmental::default().name(), Emmental::default().extensions()) {
+        debug(Emmental::default(), source, io)
+    } else if is(
+        Mascarpone::default().name(),
+        Mascarpone::default().extensions(),
+    ) {
+        debug(Mascarpone::default(), source, io)
+    } else {
+        Err(format!("can't tell the language of {}", path))
+    }
+}
+
+fn debug<L: Language + 'static>(
+    language: L,
+    source: &[u8],
+    io: InputOutputPair<Cursor<Vec<u8>>, SharedOutput>,
+) -> Result<Program, String> {
+    let debuggee = Started::new(language, source, io)?;
+    Ok(Program {
+        positions: positions(&debuggee.inspect().program(0)),
+        debuggee: Box::new(debuggee),
+    })
+}
+
+fn positions(program: &[char]) -> Vec<(usize, usize)> {
+    let (mut line, mut column) = (0, 0);
+    program
+        .iter()
+        .map(|&c| {
+            let position = (line, column);
+            if c == '\n' {
+                line += 1;
+                column = 0;
+            } else {
+                column += 1;
+            }
+            position
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_program(path: &str, source: &str) -> Program {
+        load(path, None, source.as_bytes(), b"", SharedOutput::default()).unwrap()
+    }
+
+    #[test]
+    fn frame_names() {
+        // Redefines `a` as `#98.`, then evaluates it.
+        let mut program = load_program("a.emm", ";#35#57#56#46#97!#97?");
+        while program.debuggee.inspect().active_frames().len() < 2 {
+            assert!(program.debuggee.step().unwrap());
+        }
+
+        assert_eq!(program.frame_name(0), "program");
+        assert_eq!(program.frame_name(1), "operation \"#98.\"");
+    }
+
+    #[test]
+    fn symbol_positions() {
+        let program = load(
+            "a.mascarpone",
+            None,
+            "'é\n.".as_bytes(),
+            b"",
+            SharedOutput::default(),
+        );
+        assert_eq!(program.unwrap().positions, [(0, 0), (0, 1), (0, 2), (1, 0)]);
+    }
+
+    #[test]
+    fn language_is_required() {
+        assert!(load("a.txt", None, b"", b"", SharedOutput::default()).is_err());
+        assert!(load("a.txt", Some("emmental"), b"", b"", SharedOutput::default()).is_ok());
+    }
+}
@@ -0,0 +1,73 @@
+//! A debug adapter for Emmental and Mascarpone, speaking the Debug Adapter Protocol
+//! over standard input and output.
+
+mod debuggee;
+mod session;
+
+use std::{
+    io::{self, BufReader},
+    process,
+    sync::mpsc::{self, TryRecvError},
+    thread,
+};
+
+use esolang_core::framing::{read_frame, write_frame};
+use serde_json::Value;
+
+use session::Session;
+
+/// How many symbols to interpret between checking for requests.
+const BATCH_SIZE: usize = 10_000;
+
+fn main() {
+    // Requests are read on another thread, so that a running program can be paused.
+    let (sender, requests) = mpsc::channel::<Value>();
+    thread::spawn(move || {
+        let mut input = BufReader::new(io::stdin());
+        loop {
+            let body = match read_frame(&mut input) {
+                Ok(Some(body)) => body,
+                Ok(None) => return,
+                Err(e) => {
+                    eprintln!("error: could not read message: {}", e);
+                    return;
+                }
+            };
+
+            match serde_json::from_slice(&body) {
+                Ok(request) => {
+                    if sender.send(request).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => eprintln!("error: could not parse message: {}", e),
+            }
+        }
+    });
+
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+    let mut session = Session::new();
+
+    while !session.is_done() {
+        let messages = if session.is_running() {
+            match requests.try_recv() {
+                Ok(request) => session.handle(&request),
+                Err(TryRecvError::Empty) => session.advance(BATCH_SIZE),
+                Err(TryRecvError::Disconnected) => process::exit(1),
+            }
+        } else {
+            match requests.recv() {
+                Ok(request) => session.handle(&request),
+                Err(_) => process::exit(1),
+            }
+        };
+
+        for message in messages {
+            if let Err(e) = write_frame(&mut output, message.to_string().as_bytes()) {
+                eprintln!("error: could not write message: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,654 @@
+//! Handling requests from the client, independently of how they're transported.
+
+use std::{collections::HashSet, fs};
+
+use serde_json::{json, Value};
+
+use esolang_core::io::SharedOutput;
+
+use crate::debuggee::{self, Program};
+
+/// The only thread there is.
+const THREAD_ID: i64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Continue,
+    StepIn,
+    /// Stop once there are at most this many frames.
+    Next(usize),
+    /// Stop once there are fewer than this many frames.
+    StepOut(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    /// Launched, waiting for the client to finish setting breakpoints.
+    Configuring {
+        stop_on_entry: bool,
+    },
+    Running(Mode),
+    Stopped,
+    Finished,
+}
+
+#[derive(Default)]
+pub struct Session {
+    seq: i64,
+    /// Whether the client counts lines and columns from one rather than zero.
+    lines_from_one: bool,
+    columns_from_one: bool,
+    path: String,
+    program: Option<Program>,
+    output: SharedOutput,
+    /// Offsets of symbols in the loaded program.
+    breakpoints: HashSet<usize>,
+    status: Option<Status>,
+    done: bool,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            lines_from_one: true,
+            columns_from_one: true,
+            ..Self::default()
+        }
+    }
+
+    /// Whether the program is running, and `advance` should be called while there
+    /// are no requests to handle.
+    pub fn is_running(&self) -> bool {
+        matches!(self.status, Some(Status::Running(_)))
+    }
+
+    /// Whether the client has disconnected.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Handle a request, returning the messages to send back.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let command = message["command"].as_str().unwrap_or_default();
+        let arguments = &message["arguments"];
+
+        let mut messages = Vec::new();
+        let result = self.request(command, arguments, &mut messages);
+        let (success, body) = match result {
+            Ok(body) => (json!(true), json!({ "body": body })),
+            Err(error) => (json!(false), json!({ "message": error })),
+        };
+
+        let mut response = json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": message["seq"],
+            "success": success,
+            "command": command,
+        });
+        response
+            .as_object_mut()
+            .unwrap()
+            .extend(body.as_object().unwrap().clone());
+
+        // Events caused by the request come after the response.
+        let mut events = messages;
+        for event in &mut events {
+            event["seq"] = json!(self.next_seq());
+        }
+        let mut messages = vec![response];
+        messages.extend(events);
+        messages
+    }
+
+    /// Run the program for at most `max_steps` symbols, returning the events that
+    /// happened.
+    pub fn advance(&mut self, max_steps: usize) -> Vec<Value> {
+        let mut events = Vec::new();
+        let mode = match self.status {
+            Some(Status::Running(mode)) => mode,
+            _ => return events,
+        };
+        let program = self.program.as_mut().expect("running without a program");
+
+        let mut stop = None;
+        let mut ended = None;
+        for _ in 0..max_steps {
+            match program.debuggee.step() {
+                Ok(true) => (),
+                Ok(false) => {
+                    ended = Some(Ok(()));
+                    break;
+                }
+                Err(report) => {
+                    ended = Some(Err(report));
+                    break;
+                }
+            }
+
+            let frames = program.debuggee.inspect().active_frames();
+            let at_breakpoint = frames.len() == 1 && self.breakpoints.contains(&frames[0].offset);
+            stop = match mode {
+                // Symbols are only ever interpreted as part of some frame.
+                _ if frames.is_empty() => None,
+                _ if at_breakpoint => Some("breakpoint"),
+                Mode::Continue => None,
+                Mode::StepIn => Some("step"),
+                Mode::Next(depth) if frames.len() <= depth => Some("step"),
+                Mode::StepOut(depth) if frames.len() < depth => Some("step"),
+                Mode::Next(_) | Mode::StepOut(_) => None,
+            };
+            if stop.is_some() {
+                break;
+            }
+        }
+
+        self.flush_output(&mut events);
+        match (ended, stop) {
+            (Some(result), _) => self.finish(result, &mut events),
+            (None, Some(reason)) => self.stop(reason, &mut events),
+            (None, None) => (),
+        }
+
+        for event in &mut events {
+            event["seq"] = json!(self.next_seq());
+        }
+        events
+    }
+
+    fn request(
+        &mut self,
+        command: &str,
+        arguments: &Value,
+        events: &mut Vec<Value>,
+    ) -> Result<Value, String> {
+        match command {
+            "initialize" => {
+                self.lines_from_one = arguments["linesStartAt1"].as_bool().unwrap_or(true);
+                self.columns_from_one = arguments["columnsStartAt1"].as_bool().unwrap_or(true);
+                Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsTerminateRequest": true,
+                }))
+            }
+            "launch" => {
+                let path = arguments["program"]
+                    .as_str()
+                    .ok_or("expected the path of a program")?;
+                let source =
+                    fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+                let input = arguments["input"].as_str().unwrap_or_default();
+                let language = arguments["language"].as_str();
+
+                self.program = Some(debuggee::load(
+                    path,
+                    language,
+                    &source,
+                    input.as_bytes(),
+                    self.output.clone(),
+                )?);
+                self.path = path.to_owned();
+                self.status = Some(Status::Configuring {
+                    stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+                });
+                events.push(event("initialized", Value::Null));
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => {
+                let program = self.program.as_ref().ok_or("no program was launched")?;
+                let requested = arguments["breakpoints"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+
+                self.breakpoints.clear();
+                let mut breakpoints = Vec::new();
+                for breakpoint in requested {
+                    let line = breakpoint["line"].as_u64().unwrap_or_default() as usize;
+                    let column = breakpoint["column"].as_u64().map(|c| c as usize);
+                    let line = line.wrapping_sub(self.lines_from_one as usize);
+                    let column = column.map(|c| c.wrapping_sub(self.columns_from_one as usize));
+
+                    // Without a column, the breakpoint goes on the first symbol of
+                    // the line.
+                    let offset = program
+                        .positions
+                        .iter()
+                        .position(|&(l, c)| l == line && column.is_none_or(|column| c == column));
+                    match offset {
+                        Some(offset) => {
+                            self.breakpoints.insert(offset);
+                            breakpoints.push(self.breakpoint(Some(offset)));
+                        }
+                        None => breakpoints.push(self.breakpoint(None)),
+                    }
+                }
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "configurationDone" => {
+                match self.status {
+                    Some(Status::Configuring { stop_on_entry }) => {
+                        let at_breakpoint = self.breakpoints.contains(&0);
+                        if stop_on_entry || at_breakpoint {
+                            let reason = if stop_on_entry { "entry" } else { "breakpoint" };
+                            self.stop(reason, events);
+                        } else {
+                            self.status = Some(Status::Running(Mode::Continue));
+                        }
+                    }
+                    _ => return Err("no program was launched".to_owned()),
+                }
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({
+                "threads": [{ "id": THREAD_ID, "name": "main" }],
+            })),
+            "stackTrace" => {
+                let program = self.program.as_ref().ok_or("no program was launched")?;
+                let frames = program.debuggee.inspect().active_frames();
+                let stack_frames = frames
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(id, frame)| {
+                        let mut stack_frame = json!({
+                            "id": id,
+                            "name": program.frame_name(id),
+                            "line": 0,
+                            "column": 0,
+                        });
+                        // Only the loaded program has a source to point at. Frames
+                        // other than the innermost one point at the symbol they're
+                        // interpreting, rather than the next one.
+                        if id == 0 {
+                            let offset = if id + 1 == frames.len() {
+                                frame.offset
+                            } else {
+                                frame.offset - 1
+                            };
+                            let (line, column) = program.positions[offset];
+                            stack_frame["source"] = json!({ "path": self.path });
+                            stack_frame["line"] = json!(line + self.lines_from_one as usize);
+                            stack_frame["column"] = json!(column + self.columns_from_one as usize);
+                        }
+                        stack_frame
+                    })
+                    .collect::<Vec<_>>();
+                Ok(json!({ "stackFrames": stack_frames, "totalFrames": frames.len() }))
+            }
+            "scopes" => {
+                let program = self.program.as_ref().ok_or("no program was launched")?;
+                // The scopes are the same for every frame.
+                let scopes = program
+                    .debuggee
+                    .inspect()
+                    .scopes()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, scope)| {
+                        json!({
+                            "name": scope.name,
+                            "variablesReference": i + 1,
+                            "namedVariables": scope.entries.len(),
+                            "expensive": false,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Ok(json!({ "scopes": scopes }))
+            }
+            "variables" => {
+                let program = self.program.as_ref().ok_or("no program was launched")?;
+                let reference = arguments["variablesReference"].as_u64().unwrap_or_default();
+                let variables = program
+                    .debuggee
+                    .inspect()
+                    .scopes()
+                    .into_iter()
+                    .nth((reference as usize).wrapping_sub(1))
+                    .map(|scope| scope.entries)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(name, value)| {
+                        json!({ "name": name, "value": value, "variablesReference": 0 })
+                    })
+                    .collect::<Vec<_>>();
+                Ok(json!({ "variables": variables }))
+            }
+            "continue" => {
+                self.resume(Mode::Continue)?;
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" | "stepIn" | "stepOut" => {
+                let depth = self.program.as_ref().map_or(0, |program| {
+                    program.debuggee.inspect().active_frames().len()
+                });
+                self.resume(match command {
+                    "next" => Mode::Next(depth),
+                    "stepIn" => Mode::StepIn,
+                    _ => Mode::StepOut(depth),
+                })?;
+                Ok(Value::Null)
+            }
+            "pause" => {
+                if self.is_running() {
+                    self.stop("pause", events);
+                }
+                Ok(Value::Null)
+            }
+            "terminate" => {
+                if self.status != Some(Status::Finished) {
+                    self.status = Some(Status::Finished);
+                    events.push(event("terminated", Value::Null));
+                }
+                Ok(Value::Null)
+            }
+            "disconnect" => {
+                self.done = true;
+                Ok(Value::Null)
+            }
+            _ => Err(format!("unsupported command {}", command)),
+        }
+    }
+
+    fn resume(&mut self, mode: Mode) -> Result<(), String> {
+        match self.status {
+            Some(Status::Stopped) => {
+                self.status = Some(Status::Running(mode));
+                Ok(())
+            }
+            _ => Err("the program is not stopped".to_owned()),
+        }
+    }
+
+    fn stop(&mut self, reason: &str, events: &mut Vec<Value>) {
+        self.status = Some(Status::Stopped);
+        events.push(event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        ));
+    }
+
+    fn finish(&mut self, result: Result<(), String>, events: &mut Vec<Value>) {
+        let exit_code = match result {
+            Ok(()) => 0,
+            Err(report) => {
+                events.push(event(
+                    "output",
+                    json!({ "category": "stderr", "output": report }),
+                ));
+                1
+            }
+        };
+
+        self.status = Some(Status::Finished);
+        events.push(event("exited", json!({ "exitCode": exit_code })));
+        events.push(event("terminated", Value::Null));
+    }
+
+    fn flush_output(&mut self, events: &mut Vec<Value>) {
+        let output = self.output.take();
+        if !output.is_empty() {
+            events.push(event(
+                "output",
+                json!({
+                    "category": "stdout",
+                    "output": String::from_utf8_lossy(&output),
+                }),
+            ));
+        }
+    }
+
+    fn breakpoint(&self, offset: Option<usize>) -> Value {
+        let positions = &self.program.as_ref().unwrap().positions;
+        match offset {
+            Some(offset) => {
+                let (line, column) = positions[offset];
+                json!({
+                    "verified": true,
+                    "line": line + self.lines_from_one as usize,
+                    "column": column + self.columns_from_one as usize,
+                })
+            }
+            None => json!({ "verified": false, "message": "no symbol there" }),
+        }
+    }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+}
+
+fn event(name: &str, body: Value) -> Value {
+    let mut event = json!({ "type": "event", "event": name });
+    if !body.is_null() {
+        event["body"] = body;
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf, process};
+
+    use super::*;
+
+    struct TempProgram(PathBuf);
+
+    impl TempProgram {
+        fn new(name: &str, source: &str) -> Self {
+            let path = env::temp_dir().join(format!("esolangs-dap-{}-{}", process::id(), name));
+            fs::write(&path, source).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempProgram {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn request(session: &mut Session, command: &str, arguments: Value) -> Vec<Value> {
+        session.handle(&json!({
+            "seq": 1,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        }))
+    }
+
+    fn launch(session: &mut Session, program: &TempProgram, stop_on_entry: bool) {
+        request(session, "initialize", json!({}));
+        let messages = request(
+            session,
+            "launch",
+            json!({ "program": program.0, "stopOnEntry": stop_on_entry }),
+        );
+        assert_eq!(messages[0]["success"], true, "{}", messages[0]);
+        assert_eq!(messages[1]["event"], "initialized");
+    }
+
+    fn events(messages: &[Value]) -> Vec<&str> {
+        messages
+            .iter()
+            .filter_map(|message| message["event"].as_str())
+            .collect()
+    }
+
+    /// Run until the program stops or ends, collecting everything that happens.
+    fn run(session: &mut Session) -> Vec<Value> {
+        let mut messages = Vec::new();
+        while session.is_running() {
+            messages.extend(session.advance(1000));
+        }
+        messages
+    }
+
+    #[test]
+    fn stop_at_breakpoint() {
+        let program = TempProgram::new("break.mascarpone", "'a.\n'b.\n'c.");
+        let mut session = Session::new();
+        launch(&mut session, &program, false);
+
+        let messages = request(
+            &mut session,
+            "setBreakpoints",
+            json!({
+                "source": { "path": program.0 },
+                "breakpoints": [{ "line": 2 }, { "line": 5 }],
+            }),
+        );
+        let breakpoints = &messages[0]["body"]["breakpoints"];
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[1]["verified"], false);
+
+        request(&mut session, "configurationDone", json!({}));
+        let messages = run(&mut session);
+        assert_eq!(events(&messages), ["output", "stopped"]);
+        assert_eq!(messages[0]["body"]["output"], "a");
+        assert_eq!(messages[1]["body"]["reason"], "breakpoint");
+
+        let messages = request(&mut session, "stackTrace", json!({ "threadId": 1 }));
+        let frame = &messages[0]["body"]["stackFrames"][0];
+        assert_eq!((&frame["line"], &frame["column"]), (&json!(2), &json!(1)));
+
+        request(&mut session, "continue", json!({ "threadId": 1 }));
+        let messages = run(&mut session);
+        assert_eq!(events(&messages), ["output", "exited", "terminated"]);
+        assert_eq!(messages[0]["body"]["output"], "bc");
+        assert_eq!(messages[1]["body"]["exitCode"], 0);
+    }
+
+    #[test]
+    fn step_over_and_into_operations() {
+        // Defines `a` as `#98.`, then evaluates it twice.
+        let program = TempProgram::new("step.emm", ";#35#57#56#46#97!#97?#97?");
+        let mut session = Session::new();
+        launch(&mut session, &program, true);
+
+        let messages = request(&mut session, "configurationDone", json!({}));
+        assert_eq!(messages[1]["body"]["reason"], "entry");
+
+        let depth = |session: &mut Session| {
+            let messages = request(session, "stackTrace", json!({ "threadId": 1 }));
+            messages[0]["body"]["totalFrames"].as_u64().unwrap()
+        };
+
+        // Step up to the first `?`.
+        for _ in 0..20 {
+            request(&mut session, "next", json!({ "threadId": 1 }));
+            run(&mut session);
+        }
+        assert_eq!(depth(&mut session), 1);
+
+        request(&mut session, "stepIn", json!({ "threadId": 1 }));
+        run(&mut session);
+        assert_eq!(depth(&mut session), 2);
+
+        request(&mut session, "stepOut", json!({ "threadId": 1 }));
+        let messages = run(&mut session);
+        assert_eq!(depth(&mut session), 1);
+        assert_eq!(events(&messages), ["output", "stopped"]);
+        assert_eq!(messages[0]["body"]["output"], "b");
+
+        // Stepping over the second `?` finishes the program.
+        let mut messages = Vec::new();
+        for _ in 0..4 {
+            request(&mut session, "next", json!({ "threadId": 1 }));
+            messages = run(&mut session);
+        }
+        assert_eq!(events(&messages), ["output", "exited", "terminated"]);
+    }
+
+    #[test]
+    fn step_into_tail_performed_operation() {
+        // Defines `q` as `.` and `p` as `q`, then performs `p` on "ab".
+        let program = TempProgram::new("tail.mascarpone", "v[.]v*'q<^v[q]v*'p<^'a'bp");
+        let mut session = Session::new();
+        launch(&mut session, &program, true);
+        request(&mut session, "configurationDone", json!({}));
+
+        let frames = |session: &mut Session| {
+            let messages = request(session, "stackTrace", json!({ "threadId": 1 }));
+            messages[0]["body"]["stackFrames"].clone()
+        };
+
+        for _ in 0..25 {
+            request(&mut session, "stepIn", json!({ "threadId": 1 }));
+            run(&mut session);
+        }
+        let stack_frames = frames(&mut session);
+        assert_eq!(stack_frames.as_array().unwrap().len(), 2);
+        assert_eq!(stack_frames[1]["column"], 25);
+
+        // `p` ends by performing `q`, which takes over its frame but not the one of
+        // the program that was loaded.
+        request(&mut session, "stepIn", json!({ "threadId": 1 }));
+        run(&mut session);
+        let stack_frames = frames(&mut session);
+        assert_eq!(stack_frames.as_array().unwrap().len(), 2);
+        assert_eq!(stack_frames[1]["column"], 25);
+        assert_eq!(stack_frames[0]["line"], 0);
+
+        request(&mut session, "next", json!({ "threadId": 1 }));
+        let messages = run(&mut session);
+        assert_eq!(events(&messages), ["output", "exited", "terminated"]);
+        assert_eq!(messages[0]["body"]["output"], "b");
+    }
+
+    #[test]
+    fn inspect_variables() {
+        let program = TempProgram::new("vars.emm", "#65#66^");
+        let mut session = Session::new();
+        launch(&mut session, &program, false);
+        request(&mut session, "configurationDone", json!({}));
+        request(&mut session, "pause", json!({ "threadId": 1 }));
+
+        let messages = request(&mut session, "scopes", json!({ "frameId": 0 }));
+        let scopes = &messages[0]["body"]["scopes"];
+        assert_eq!(scopes[0]["name"], "Stack");
+        assert_eq!(scopes[1]["name"], "Queue");
+
+        for _ in 0..7 {
+            request(&mut session, "stepIn", json!({ "threadId": 1 }));
+            run(&mut session);
+        }
+        let messages = request(
+            &mut session,
+            "variables",
+            json!({ "variablesReference": 1 }),
+        );
+        let variables = &messages[0]["body"]["variables"];
+        assert_eq!(variables[0]["value"], "66 'B'");
+        assert_eq!(variables[1]["value"], "65 'A'");
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let program = TempProgram::new("error.emm", "!");
+        let mut session = Session::new();
+        launch(&mut session, &program, false);
+        request(&mut session, "configurationDone", json!({}));
+
+        let messages = run(&mut session);
+        assert_eq!(events(&messages), ["output", "exited", "terminated"]);
+        assert_eq!(messages[0]["body"]["category"], "stderr");
+        assert_eq!(messages[1]["body"]["exitCode"], 1);
+    }
+
+    #[test]
+    fn launch_errors() {
+        let mut session = Session::new();
+        let messages = request(
+            &mut session,
+            "launch",
+            json!({ "program": "/nonexistent.emm" }),
+        );
+        assert_eq!(messages[0]["success"], false);
+
+        let messages = request(&mut session, "foo", json!({}));
+        assert_eq!(messages[0]["success"], false);
+    }
+}
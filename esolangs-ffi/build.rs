@@ -0,0 +1,13 @@
+use std::{env, path::Path};
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    match cbindgen::generate(&crate_dir) {
+        Ok(bindings) => {
+            bindings.write_to_file(Path::new(&out_dir).join("esolangs.h"));
+        }
+        Err(e) => println!("cargo:warning=could not generate the C header: {}", e),
+    }
+}
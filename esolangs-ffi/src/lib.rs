@@ -0,0 +1,662 @@
+//! A C interface for hosting the interpreters in this workspace, without having to
+//! spawn a process. The header is generated into `esolangs.h` in the build's
+//! `OUT_DIR`.
+//!
+//! A host creates an interpreter for one of the languages, loads a program into it,
+//! and runs it for a given number of steps at a time, feeding it input and reading
+//! its output in between. When the program reads input that hasn't been fed yet, it
+//! waits for it, until the host ends the input with `esolang_end_input`.
+//!
+//! Functions that can fail return a status. After a program fails, a description of
+//! the error is available from `esolang_last_error`, and the program has to be
+//! loaded again to run it again.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    ffi::CString,
+    io::{self, Read, Write},
+    os::raw::c_char,
+    ptr,
+    rc::Rc,
+    slice,
+};
+
+use emmental::language::Emmental;
+use esolang_core::{
+    io::InputOutputPair,
+    language::{Execution, Language},
+    limits::Limits,
+};
+use mascarpone::language::Mascarpone;
+
+/// The result of a call. Errors from the interpreters themselves each have their
+/// own status; Emmental only tells apart going over one of its limits.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EsolangStatus {
+    Ok = 0,
+    /// The budget ran out before the program was done. Run it again to continue.
+    OutOfBudget = 1,
+    /// A null pointer was passed where one isn't allowed.
+    InvalidArgument = 2,
+    /// There's no program to run, because none was loaded or the last one failed.
+    NoProgram = 3,
+    /// The program is waiting for input. Feed it with `esolang_feed_input` or end
+    /// it with `esolang_end_input`, then run the program again to continue.
+    NeedsInput = 4,
+
+    EmmentalError = 100,
+    EmmentalStackLimit = 101,
+    EmmentalQueueLimit = 102,
+    EmmentalSymbolLimit = 103,
+
+    MascarponeNoParent = 200,
+    MascarponeNullInterpreter = 201,
+    MascarponeEmptyStack = 202,
+    MascarponeWrongElementType = 203,
+    MascarponeWrongInterpreterVariant = 204,
+    MascarponeMalformedString = 205,
+    MascarponeCancelled = 206,
+    MascarponeIOError = 207,
+    MascarponeInvalidInput = 208,
+    MascarponeUnrepresentableSymbol = 209,
+    MascarponeSyntax = 210,
+    MascarponeStackLimit = 211,
+    MascarponeTowerLimit = 212,
+    MascarponeSymbolLimit = 213,
+}
+
+/// A byte buffer shared between a handle and the program running in it.
+#[derive(Debug, Clone, Default)]
+struct Buffer(Rc<RefCell<VecDeque<u8>>>);
+
+/// Input shared between a handle and the program running in it, which has the
+/// program wait for more until it's ended.
+#[derive(Debug, Clone, Default)]
+struct Input {
+    buffer: Buffer,
+    ended: Rc<Cell<bool>>,
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.buffer.read(buf)? {
+            0 if !buf.is_empty() && !self.ended.get() => Err(io::ErrorKind::WouldBlock.into()),
+            len => Ok(len),
+        }
+    }
+}
+
+impl Read for Buffer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Limits on how much memory a program may take up. Zero means there's no limit.
+/// Limits that don't apply to a language are ignored.
+///
+/// Interpreters start out without limits, so untrusted programs should only be
+/// loaded after setting these with `esolang_set_limits`, and run with a budget.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EsolangLimits {
+    /// The number of elements on the stack.
+    pub stack: usize,
+    /// The number of symbols in the queue.
+    pub queue: usize,
+    /// The number of interpreters in the tower of any interpreter.
+    pub tower: usize,
+    /// The number of symbols held on to in total.
+    pub symbols: usize,
+}
+
+impl From<EsolangLimits> for Limits {
+    fn from(limits: EsolangLimits) -> Self {
+        let limit = |limit| Some(limit).filter(|&limit| limit > 0);
+        Self {
+            stack: limit(limits.stack),
+            queue: limit(limits.queue),
+            tower: limit(limits.tower),
+            symbols: limit(limits.symbols),
+        }
+    }
+}
+
+struct Failure {
+    status: EsolangStatus,
+    message: String,
+}
+
+/// A language we know the statuses of the errors of.
+trait Embedded: Language {
+    fn status(error: &Self::Error) -> EsolangStatus;
+    fn set_limits(&mut self, limits: Limits);
+}
+
+impl Embedded for Emmental {
+    fn status(error: &emmental::Error) -> EsolangStatus {
+        use emmental::Error::*;
+
+        match error {
+            Failed(_) => EsolangStatus::EmmentalError,
+            StackLimit { .. } => EsolangStatus::EmmentalStackLimit,
+            QueueLimit { .. } => EsolangStatus::EmmentalQueueLimit,
+            SymbolLimit { .. } => EsolangStatus::EmmentalSymbolLimit,
+            NeedsInput => EsolangStatus::NeedsInput,
+        }
+    }
+
+    fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+}
+
+impl Embedded for Mascarpone {
+    fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    fn status(error: &mascarpone::Error) -> EsolangStatus {
+        use mascarpone::Error::*;
+
+        match error.root() {
+            NoParent => EsolangStatus::MascarponeNoParent,
+            NullInterpreter => EsolangStatus::MascarponeNullInterpreter,
+            EmptyStack => EsolangStatus::MascarponeEmptyStack,
+            WrongElementType { .. } => EsolangStatus::MascarponeWrongElementType,
+            WrongInterpreterVariant => EsolangStatus::MascarponeWrongInterpreterVariant,
+            MalformedString => EsolangStatus::MascarponeMalformedString,
+            // We never give the interpreter a budget of its own.
+            OutOfBudget => EsolangStatus::OutOfBudget,
+            Cancelled => EsolangStatus::MascarponeCancelled,
+            NeedsInput => EsolangStatus::NeedsInput,
+            StackLimit { .. } => EsolangStatus::MascarponeStackLimit,
+            TowerLimit { .. } => EsolangStatus::MascarponeTowerLimit,
+            SymbolLimit { .. } => EsolangStatus::MascarponeSymbolLimit,
+            IOError(_) => EsolangStatus::MascarponeIOError,
+            InvalidInput { .. } => EsolangStatus::MascarponeInvalidInput,
+            UnrepresentableSymbol { .. } => EsolangStatus::MascarponeUnrepresentableSymbol,
+            Syntax { .. } => EsolangStatus::MascarponeSyntax,
+            Located { .. } => unreachable!("root errors aren't located"),
+        }
+    }
+}
+
+fn failure<L: Embedded>(language: &L, error: &L::Error) -> Failure {
+    Failure {
+        status: L::status(error),
+        message: language.report(error),
+    }
+}
+
+/// The parts of `Language` and `Execution` that we need, without the associated
+/// types, so a handle can hold any language.
+trait Loader {
+    fn load(
+        &self,
+        source: &[u8],
+        input: Input,
+        output: Buffer,
+    ) -> Result<Box<dyn Machine>, Failure>;
+    fn set_limits(&mut self, limits: Limits);
+}
+
+trait Machine {
+    fn step(&mut self) -> Result<bool, Failure>;
+}
+
+struct Loaded<L: Language> {
+    language: L,
+    execution: L::Execution<Input, Buffer>,
+}
+
+impl<L> Loader for L
+where
+    L: Embedded + Clone + 'static,
+    L::Execution<Input, Buffer>: 'static,
+{
+    fn load(
+        &self,
+        source: &[u8],
+        input: Input,
+        output: Buffer,
+    ) -> Result<Box<dyn Machine>, Failure> {
+        let program = self.parse(source).map_err(|e| failure(self, &e))?;
+        let execution = self.start(program, InputOutputPair::new(input, output));
+
+        Ok(Box::new(Loaded {
+            language: self.clone(),
+            execution,
+        }))
+    }
+
+    fn set_limits(&mut self, limits: Limits) {
+        Embedded::set_limits(self, limits)
+    }
+}
+
+impl<L: Embedded> Machine for Loaded<L> {
+    fn step(&mut self) -> Result<bool, Failure> {
+        self.execution
+            .step()
+            .map_err(|e| failure(&self.language, &e))
+    }
+}
+
+/// An interpreter for one of the languages, along with its program and IO.
+pub struct EsolangInterpreter {
+    language: Box<dyn Loader>,
+    machine: Option<Box<dyn Machine>>,
+    input: Input,
+    output: Buffer,
+    last_error: Option<CString>,
+}
+
+impl EsolangInterpreter {
+    fn new(language: impl Loader + 'static) -> Box<Self> {
+        Box::new(Self {
+            language: Box::new(language),
+            machine: None,
+            input: Input::default(),
+            output: Buffer::default(),
+            last_error: None,
+        })
+    }
+
+    fn fail(&mut self, failure: Failure) -> EsolangStatus {
+        let message = failure.message.trim_end().replace('\0', "\\0");
+        self.last_error = CString::new(message).ok();
+        failure.status
+    }
+}
+
+/// Create an Emmental interpreter. It must be destroyed with `esolang_destroy`.
+#[no_mangle]
+pub extern "C" fn esolang_emmental_new() -> *mut EsolangInterpreter {
+    Box::into_raw(EsolangInterpreter::new(Emmental::default()))
+}
+
+/// Create a Mascarpone interpreter, which reads and writes UTF-8. It must be
+/// destroyed with `esolang_destroy`.
+#[no_mangle]
+pub extern "C" fn esolang_mascarpone_new() -> *mut EsolangInterpreter {
+    Box::into_raw(EsolangInterpreter::new(Mascarpone::default()))
+}
+
+/// Destroy an interpreter. Passing null does nothing.
+///
+/// # Safety
+///
+/// `interp` must be null or have been created by one of the `esolang_*_new`
+/// functions, and not have been destroyed yet.
+#[no_mangle]
+pub unsafe extern "C" fn esolang_destroy(interp: *mut EsolangInterpreter) {
+    if !interp.is_null() {
+        drop(Box::from_raw(interp));
+    }
+}
+
+/// Set the limits that programs loaded from now on are run within. There are no
+/// limits until this is called.
+///
+/// # Safety
+///
+/// `interp` must be a live interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn esolang_set_limits(
+    interp: *mut EsolangInterpreter,
+    limits: EsolangLimits,
+) -> EsolangStatus {
+    match interp.as_mut() {
+        Some(interp) => {
+            interp.language.set_limits(limits.into());
+            EsolangStatus::Ok
+        }
+        None => EsolangStatus::InvalidArgument,
+    }
+}
+
+/// Load a program of `len` bytes, replacing the one that was loaded before, if
+/// any. Input and output that haven't been used yet are kept.
+///
+/// # Safety
+///
+/// `interp` must be a live interpreter, and `program` must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn esolang_load_program(
+    interp: *mut EsolangInterpreter,
+    program: *const u8,
+    len: usize,
+) -> EsolangStatus {
+    let interp = match interp.as_mut() {
+        Some(interp) if !program.is_null() => interp,
+        _ => return EsolangStatus::InvalidArgument,
+    };
+    let source = slice::from_raw_parts(program, len);
+
+    interp.machine = None;
+    interp.last_error = None;
+    match interp
+        .language
+        .load(source, interp.input.clone(), interp.output.clone())
+    {
+        Ok(machine) => {
+            interp.machine = Some(machine);
+            EsolangStatus::Ok
+        }
+        Err(failure) => interp.fail(failure),
+    }
+}
+
+/// Run the loaded program for at most `budget` steps, returning
+/// `ESOLANG_STATUS_OK` once it's done. Every symbol that gets interpreted takes a
+/// step, including those of the programs that operations run. A program that's
+/// waiting for input stays loaded, and continues where it left off when run again.
+///
+/// # Safety
+///
+/// `interp` must be a live interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn esolang_run_with_budget(
+    interp: *mut EsolangInterpreter,
+    budget: u64,
+) -> EsolangStatus {
+    let interp = match interp.as_mut() {
+        Some(interp) => interp,
+        None => return EsolangStatus::InvalidArgument,
+    };
+    let machine = match &mut interp.machine {
+        Some(machine) => machine,
+        None => return EsolangStatus::NoProgram,
+    };
+
+    for _ in 0..budget {
+        match machine.step() {
+            Ok(true) => (),
+            Ok(false) => {
+                interp.machine = None;
+                return EsolangStatus::Ok;
+            }
+            Err(failure) if failure.status == EsolangStatus::NeedsInput => {
+                return EsolangStatus::NeedsInput;
+            }
+            Err(failure) => {
+                interp.machine = None;
+                return interp.fail(failure);
+            }
+        }
+    }
+
+    EsolangStatus::OutOfBudget
+}
+
+/// Add `len` bytes to the end of the input.
+///
+/// # Safety
+///
+/// `interp` must be a live interpreter, and `input` must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn esolang_feed_input(
+    interp: *mut EsolangInterpreter,
+    input: *const u8,
+    len: usize,
+) -> EsolangStatus {
+    match interp.as_mut() {
+        Some(interp) if !input.is_null() => {
+            let input = slice::from_raw_parts(input, len);
+            interp.input.buffer.0.borrow_mut().extend(input);
+            EsolangStatus::Ok
+        }
+        _ => EsolangStatus::InvalidArgument,
+    }
+}
+
+/// End the input, so programs read the end of it once they've read what was fed,
+/// instead of waiting for more. Input fed afterwards is still read first.
+///
+/// # Safety
+///
+/// `interp` must be a live interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn esolang_end_input(interp: *mut EsolangInterpreter) -> EsolangStatus {
+    match interp.as_mut() {
+        Some(interp) => {
+            interp.input.ended.set(true);
+            EsolangStatus::Ok
+        }
+        None => EsolangStatus::InvalidArgument,
+    }
+}
+
+/// Move up to `capacity` bytes of output that haven't been read yet into `buffer`,
+/// returning how many there were.
+///
+/// # Safety
+///
+/// `interp` must be a live interpreter, and `buffer` must point to `capacity`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn esolang_read_output(
+    interp: *mut EsolangInterpreter,
+    buffer: *mut u8,
+    capacity: usize,
+) -> usize {
+    match interp.as_mut() {
+        Some(interp) if !buffer.is_null() => {
+            let buffer = slice::from_raw_parts_mut(buffer, capacity);
+            interp.output.0.borrow_mut().read(buffer).unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
+/// A description of the last error that a program caused, or null if it didn't
+/// cause one. The string is owned by the interpreter, and is valid until the next
+/// call that takes it.
+///
+/// # Safety
+///
+/// `interp` must be a live interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn esolang_last_error(interp: *const EsolangInterpreter) -> *const c_char {
+    match interp
+        .as_ref()
+        .and_then(|interp| interp.last_error.as_ref())
+    {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    struct Handle(*mut EsolangInterpreter);
+
+    impl Handle {
+        fn load(&self, program: &[u8]) -> EsolangStatus {
+            unsafe { esolang_load_program(self.0, program.as_ptr(), program.len()) }
+        }
+
+        fn run(&self, budget: u64) -> EsolangStatus {
+            unsafe { esolang_run_with_budget(self.0, budget) }
+        }
+
+        fn feed(&self, input: &[u8]) -> EsolangStatus {
+            unsafe { esolang_feed_input(self.0, input.as_ptr(), input.len()) }
+        }
+
+        fn end_input(&self) -> EsolangStatus {
+            unsafe { esolang_end_input(self.0) }
+        }
+
+        fn output(&self) -> Vec<u8> {
+            let mut buffer = [0; 4];
+            let mut output = Vec::new();
+            loop {
+                let len = unsafe { esolang_read_output(self.0, buffer.as_mut_ptr(), buffer.len()) };
+                if len == 0 {
+                    return output;
+                }
+                output.extend_from_slice(&buffer[..len]);
+            }
+        }
+
+        fn last_error(&self) -> Option<String> {
+            let message = unsafe { esolang_last_error(self.0) };
+            if message.is_null() {
+                None
+            } else {
+                let message = unsafe { CStr::from_ptr(message) };
+                Some(message.to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe { esolang_destroy(self.0) }
+        }
+    }
+
+    #[test]
+    fn run_emmental() {
+        let interp = Handle(esolang_emmental_new());
+
+        assert_eq!(interp.load(b"#72.#105.,."), EsolangStatus::Ok);
+        assert_eq!(interp.feed(b"!"), EsolangStatus::Ok);
+        assert_eq!(interp.run(u64::MAX), EsolangStatus::Ok);
+        assert_eq!(interp.output(), b"Hi!");
+        assert_eq!(interp.last_error(), None);
+    }
+
+    #[test]
+    fn run_mascarpone_in_parts() {
+        let interp = Handle(esolang_mascarpone_new());
+
+        assert_eq!(interp.load("'é.,.".as_bytes()), EsolangStatus::Ok);
+        assert_eq!(interp.run(2), EsolangStatus::OutOfBudget);
+        assert_eq!(interp.run(1), EsolangStatus::OutOfBudget);
+        assert_eq!(interp.output(), "é".as_bytes());
+
+        assert_eq!(interp.feed(b"?"), EsolangStatus::Ok);
+        assert_eq!(interp.run(10), EsolangStatus::Ok);
+        assert_eq!(interp.output(), b"?");
+        assert_eq!(interp.run(10), EsolangStatus::NoProgram);
+    }
+
+    #[test]
+    fn wait_for_input() {
+        let interp = Handle(esolang_mascarpone_new());
+
+        assert_eq!(interp.load(b",.,."), EsolangStatus::Ok);
+        assert_eq!(interp.run(10), EsolangStatus::NeedsInput);
+        assert_eq!(interp.feed(&"€".as_bytes()[..2]), EsolangStatus::Ok);
+        assert_eq!(interp.run(10), EsolangStatus::NeedsInput);
+        assert_eq!(interp.feed(&"€".as_bytes()[2..]), EsolangStatus::Ok);
+        assert_eq!(interp.run(10), EsolangStatus::NeedsInput);
+        assert_eq!(interp.output(), "€".as_bytes());
+        assert_eq!(interp.last_error(), None);
+
+        assert_eq!(interp.end_input(), EsolangStatus::Ok);
+        assert_eq!(interp.run(10), EsolangStatus::MascarponeIOError);
+
+        let interp = Handle(esolang_emmental_new());
+        assert_eq!(interp.load(b",."), EsolangStatus::Ok);
+        assert_eq!(interp.run(10), EsolangStatus::NeedsInput);
+        assert_eq!(interp.feed(b"a"), EsolangStatus::Ok);
+        assert_eq!(interp.run(10), EsolangStatus::Ok);
+        assert_eq!(interp.output(), b"a");
+    }
+
+    #[test]
+    fn errors_have_status_and_message() {
+        let interp = Handle(esolang_mascarpone_new());
+
+        assert_eq!(interp.load(b"'a.."), EsolangStatus::Ok);
+        assert_eq!(interp.run(100), EsolangStatus::MascarponeEmptyStack);
+        assert!(interp
+            .last_error()
+            .unwrap()
+            .starts_with("error: unexpected empty stack\n  at offset 3"));
+        assert_eq!(interp.run(100), EsolangStatus::NoProgram);
+
+        assert_eq!(interp.load(b"\xff"), EsolangStatus::MascarponeSyntax);
+        assert_eq!(interp.load(b""), EsolangStatus::Ok);
+        assert_eq!(interp.last_error(), None);
+
+        let interp = Handle(esolang_emmental_new());
+        assert_eq!(interp.load(b"."), EsolangStatus::Ok);
+        assert_eq!(interp.run(100), EsolangStatus::EmmentalError);
+        assert!(interp.last_error().is_some());
+    }
+
+    #[test]
+    fn limits_have_statuses() {
+        let limits = EsolangLimits {
+            stack: 2,
+            ..EsolangLimits::default()
+        };
+
+        let interp = Handle(esolang_emmental_new());
+        assert_eq!(interp.load(b"###"), EsolangStatus::Ok);
+        assert_eq!(interp.run(100), EsolangStatus::Ok);
+        assert_eq!(
+            unsafe { esolang_set_limits(interp.0, limits) },
+            EsolangStatus::Ok
+        );
+        assert_eq!(interp.load(b"###"), EsolangStatus::Ok);
+        assert_eq!(interp.run(100), EsolangStatus::EmmentalStackLimit);
+
+        let interp = Handle(esolang_mascarpone_new());
+        unsafe { esolang_set_limits(interp.0, limits) };
+        assert_eq!(interp.load(b"'a'b'c"), EsolangStatus::Ok);
+        assert_eq!(interp.run(100), EsolangStatus::MascarponeStackLimit);
+
+        unsafe {
+            assert_eq!(
+                esolang_set_limits(ptr::null_mut(), limits),
+                EsolangStatus::InvalidArgument
+            );
+        }
+    }
+
+    #[test]
+    fn null_arguments() {
+        let interp = Handle(esolang_emmental_new());
+
+        unsafe {
+            assert_eq!(
+                esolang_load_program(interp.0, ptr::null(), 0),
+                EsolangStatus::InvalidArgument
+            );
+            assert_eq!(
+                esolang_run_with_budget(ptr::null_mut(), 1),
+                EsolangStatus::InvalidArgument
+            );
+            assert_eq!(esolang_read_output(interp.0, ptr::null_mut(), 1), 0);
+            assert_eq!(
+                esolang_end_input(ptr::null_mut()),
+                EsolangStatus::InvalidArgument
+            );
+            assert!(esolang_last_error(ptr::null()).is_null());
+            esolang_destroy(ptr::null_mut());
+        }
+    }
+}
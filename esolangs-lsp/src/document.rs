@@ -0,0 +1,182 @@
+//! Open documents, and what we know about them. Everything here works in terms of
+//! offsets in characters; converting from and to LSP positions happens at the edges.
+
+use std::ops::Range;
+
+use emmental::language::Emmental;
+use esolang_core::language::Language as _;
+use mascarpone::language::Mascarpone;
+
+use crate::{emmental as emm, mascarpone as masc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Emmental,
+    Mascarpone,
+}
+
+impl Language {
+    /// Figure out the language of a document from the language ID the client gave
+    /// it, or else from the extension of its URI.
+    pub fn detect(uri: &str, language_id: &str) -> Option<Self> {
+        let languages = [
+            (
+                Self::Emmental,
+                Emmental::default().name(),
+                Emmental::default().extensions(),
+            ),
+            (
+                Self::Mascarpone,
+                Mascarpone::default().name(),
+                Mascarpone::default().extensions(),
+            ),
+        ];
+
+        let extension = uri.rsplit_once('.').map(|(_, ext)| ext);
+        languages
+            .iter()
+            .find(|(_, name, _)| *name == language_id)
+            .or_else(|| {
+                languages
+                    .iter()
+                    .find(|(_, _, extensions)| extension.is_some_and(|e| extensions.contains(&e)))
+            })
+            .map(|&(lang, _, _)| lang)
+    }
+}
+
+/// The values match the LSP's `DiagnosticSeverity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error = 1,
+    Warning = 2,
+    Hint = 4,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub span: Range<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// The result of a quick static pass over a program. As programs can redefine what
+/// symbols mean, this assumes they keep the meaning they have initially.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    /// The offsets of matching brackets.
+    pub pairs: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hover {
+    /// Markdown.
+    pub text: String,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub language: Language,
+    chars: Vec<char>,
+    analysis: Analysis,
+}
+
+impl Document {
+    pub fn new(language: Language, text: &str) -> Self {
+        let chars = text.chars().collect::<Vec<_>>();
+        let analysis = match language {
+            Language::Emmental => emm::analyze(&chars),
+            Language::Mascarpone => masc::analyze(&chars),
+        };
+
+        Self {
+            language,
+            chars,
+            analysis,
+        }
+    }
+
+    pub fn analysis(&self) -> &Analysis {
+        &self.analysis
+    }
+
+    pub fn hover(&self, offset: usize) -> Option<Hover> {
+        match self.language {
+            Language::Emmental => emm::hover(&self.chars, offset),
+            Language::Mascarpone => masc::hover(&self.chars, offset),
+        }
+    }
+
+    /// The bracket at `offset` and the one matching it, if there is one.
+    pub fn matching_brackets(&self, offset: usize) -> Option<(usize, usize)> {
+        self.analysis
+            .pairs
+            .iter()
+            .copied()
+            .find(|&(open, close)| offset == open || offset == close)
+    }
+
+    /// The LSP position of `offset`: the line, and the column in UTF-16 code units.
+    pub fn position(&self, offset: usize) -> (u32, u32) {
+        let mut line = 0;
+        let mut column = 0;
+        for &c in self.chars.iter().take(offset) {
+            if c == '\n' {
+                line += 1;
+                column = 0;
+            } else {
+                column += c.len_utf16() as u32;
+            }
+        }
+        (line, column)
+    }
+
+    /// The offset of the character at an LSP position, if there is one.
+    pub fn offset(&self, line: u32, column: u32) -> Option<usize> {
+        let (mut current_line, mut current_column) = (0, 0);
+        for (offset, &c) in self.chars.iter().enumerate() {
+            if (current_line, current_column) == (line, column) {
+                return Some(offset);
+            }
+
+            if c == '\n' {
+                current_line += 1;
+                current_column = 0;
+            } else {
+                current_column += c.len_utf16() as u32;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_language() {
+        assert_eq!(
+            Language::detect("file:///a.emm", "plaintext"),
+            Some(Language::Emmental)
+        );
+        assert_eq!(
+            Language::detect("file:///a.emm", "mascarpone"),
+            Some(Language::Mascarpone)
+        );
+        assert_eq!(Language::detect("file:///a.txt", "plaintext"), None);
+    }
+
+    #[test]
+    fn positions_count_utf16() {
+        let doc = Document::new(Language::Mascarpone, "'𝄞.\n'é.");
+
+        assert_eq!(doc.position(2), (0, 3));
+        assert_eq!(doc.position(5), (1, 1));
+        assert_eq!(doc.offset(0, 3), Some(2));
+        assert_eq!(doc.offset(1, 1), Some(5));
+        assert_eq!(doc.offset(1, 3), None);
+    }
+}
@@ -0,0 +1,157 @@
+use std::{convert::TryFrom, ops::Range};
+
+use emmental::{
+    interpreter::{Interpreter, Operation},
+    Symbol,
+};
+
+use crate::document::{Analysis, Diagnostic, Hover, Severity};
+
+/// A `#` followed by digits, which together push a single symbol.
+struct Literal {
+    span: Range<usize>,
+    /// What the digits would give without wrapping around, if it fits.
+    exact: Option<u64>,
+    value: Symbol,
+}
+
+fn literals(chars: &[char]) -> Vec<Literal> {
+    let mut literals = Vec::new();
+    let mut offset = 0;
+
+    while offset < chars.len() {
+        if chars[offset] != '#' {
+            offset += 1;
+            continue;
+        }
+
+        let start = offset;
+        let mut exact = Some(0u64);
+        let mut value: Symbol = 0;
+        offset += 1;
+        while let Some(digit) = chars.get(offset).and_then(|c| c.to_digit(10)) {
+            exact = exact
+                .and_then(|n| n.checked_mul(10))
+                .and_then(|n| n.checked_add(digit.into()));
+            value = value.wrapping_mul(10).wrapping_add(digit as Symbol);
+            offset += 1;
+        }
+
+        literals.push(Literal {
+            span: start..offset,
+            exact,
+            value,
+        });
+    }
+
+    literals
+}
+
+pub fn analyze(chars: &[char]) -> Analysis {
+    let diagnostics = literals(chars)
+        .into_iter()
+        .filter(|literal| literal.exact != Some(literal.value.into()))
+        .map(|literal| Diagnostic {
+            message: format!(
+                "`{}` wraps around to {}",
+                chars[literal.span.clone()].iter().collect::<String>(),
+                literal.value
+            ),
+            span: literal.span,
+            severity: Severity::Warning,
+        })
+        .collect();
+
+    Analysis {
+        diagnostics,
+        pairs: Vec::new(),
+    }
+}
+
+pub fn hover(chars: &[char], offset: usize) -> Option<Hover> {
+    if let Some(literal) = literals(chars)
+        .into_iter()
+        .find(|literal| literal.span.contains(&offset) && literal.span.len() > 1)
+    {
+        let shown = match literal.value {
+            b' '..=b'~' => format!(" (`{:?}`)", literal.value as char),
+            _ => String::new(),
+        };
+        return Some(Hover {
+            text: format!("Pushes {}{}.", literal.value, shown),
+            span: literal.span,
+        });
+    }
+
+    let c = *chars.get(offset)?;
+    if c.is_whitespace() {
+        return None;
+    }
+
+    let text = match u8::try_from(c).map(|sym| Interpreter::default().lookup(sym).clone()) {
+        Ok(Operation::Primitive(primop)) => format!("`{}`: {}", c, primop.description()),
+        _ => format!("`{}` does nothing initially.", c),
+    };
+    Some(Hover {
+        text,
+        span: offset..offset + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(program: &str) -> Vec<char> {
+        program.chars().collect()
+    }
+
+    #[test]
+    fn wrapping_literals_are_reported() {
+        let analysis = analyze(&chars("#255#256.#"));
+
+        assert_eq!(
+            analysis.diagnostics,
+            [Diagnostic {
+                span: 4..8,
+                severity: Severity::Warning,
+                message: "`#256` wraps around to 0".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn hover_literal() {
+        let program = chars("#72.");
+
+        for offset in 0..3 {
+            assert_eq!(
+                hover(&program, offset),
+                Some(Hover {
+                    text: "Pushes 72 (`'H'`).".to_owned(),
+                    span: 0..3,
+                })
+            );
+        }
+        assert_eq!(
+            hover(&program, 3).unwrap().text,
+            "`.`: Pop a symbol and output it."
+        );
+    }
+
+    #[test]
+    fn hover_other_symbols() {
+        let program = chars("5 x");
+
+        assert!(hover(&program, 0)
+            .unwrap()
+            .text
+            .contains("multiply it by 10"));
+        assert_eq!(hover(&program, 1), None);
+        assert_eq!(
+            hover(&program, 2).unwrap().text,
+            "`x` does nothing initially."
+        );
+        assert_eq!(hover(&program, 3), None);
+    }
+}
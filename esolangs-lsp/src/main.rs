@@ -0,0 +1,42 @@
+//! A language server for Emmental and Mascarpone, speaking JSON-RPC over standard
+//! input and output.
+
+mod document;
+mod emmental;
+mod mascarpone;
+mod rpc;
+mod server;
+
+use std::{io, process};
+
+use server::Server;
+
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut input = stdin.lock();
+    let mut output = stdout.lock();
+    let mut server = Server::new();
+
+    loop {
+        let message = match rpc::read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => process::exit(1),
+            Err(e) => {
+                eprintln!("error: could not read message: {}", e);
+                process::exit(1);
+            }
+        };
+
+        for response in server.handle(&message) {
+            if let Err(e) = rpc::write_message(&mut output, &response) {
+                eprintln!("error: could not write message: {}", e);
+                process::exit(1);
+            }
+        }
+
+        if let Some(code) = server.exit_code() {
+            process::exit(code);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use mascarpone::operation::Intrinsic;
+
+use crate::document::{Analysis, Diagnostic, Hover, Severity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Intrinsic(Intrinsic),
+    /// The symbol after a `'`.
+    Quoted,
+    /// Part of a string, including the brackets of nested strings and the closing
+    /// bracket of the string itself.
+    InString,
+}
+
+struct Scan {
+    roles: Vec<Role>,
+    analysis: Analysis,
+}
+
+// Follows how the initial interpreter and the quoting interpreters it switches to
+// treat each symbol.
+fn scan(chars: &[char]) -> Scan {
+    let mut roles = Vec::with_capacity(chars.len());
+    let mut analysis = Analysis::default();
+    let mut open = Vec::new();
+    let mut quoting = false;
+
+    for (offset, &c) in chars.iter().enumerate() {
+        if quoting {
+            roles.push(Role::Quoted);
+            quoting = false;
+            continue;
+        }
+
+        if !open.is_empty() {
+            roles.push(Role::InString);
+            match c {
+                '[' => open.push(offset),
+                ']' => {
+                    if let Some(start) = open.pop() {
+                        analysis.pairs.push((start, offset));
+                    }
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        let op = Intrinsic::from_symbol(c).unwrap_or(Intrinsic::NoOp);
+        roles.push(Role::Intrinsic(op));
+        match op {
+            Intrinsic::QuoteString => open.push(offset),
+            Intrinsic::QuoteSymbol => quoting = true,
+            Intrinsic::NoOp if c == ']' => analysis.diagnostics.push(Diagnostic {
+                span: offset..offset + 1,
+                severity: Severity::Hint,
+                message: "`]` outside of a string does nothing".to_owned(),
+            }),
+            _ => (),
+        }
+    }
+
+    if quoting {
+        let offset = chars.len() - 1;
+        analysis.diagnostics.push(Diagnostic {
+            span: offset..offset + 1,
+            severity: Severity::Warning,
+            message: "`'` at the end of the program quotes nothing".to_owned(),
+        });
+    }
+
+    for start in open {
+        analysis.diagnostics.push(Diagnostic {
+            span: start..start + 1,
+            severity: Severity::Error,
+            message: "string is never closed".to_owned(),
+        });
+    }
+
+    analysis.pairs.sort_unstable();
+    Scan { roles, analysis }
+}
+
+pub fn analyze(chars: &[char]) -> Analysis {
+    scan(chars).analysis
+}
+
+pub fn hover(chars: &[char], offset: usize) -> Option<Hover> {
+    let c = *chars.get(offset)?;
+    let role = scan(chars).roles[offset];
+
+    let text = match role {
+        _ if c.is_whitespace() && role != Role::Quoted => return None,
+        Role::Intrinsic(Intrinsic::NoOp) => format!("`{}` does nothing initially.", c),
+        Role::Intrinsic(op) => format!("`{}` ({:?}): {}", c, op, op.description()),
+        Role::Quoted => format!("Pushes {:?}.", c),
+        Role::InString => format!("Part of a string, pushes {:?}.", c),
+    };
+    Some(Hover {
+        text,
+        span: offset..offset + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(program: &str) -> Vec<char> {
+        program.chars().collect()
+    }
+
+    #[test]
+    fn brackets_are_matched() {
+        let analysis = analyze(&chars("[a[b]'[]'][]"));
+
+        // Quotes inside strings are just symbols.
+        assert_eq!(analysis.pairs, [(0, 9), (2, 4), (6, 7), (10, 11)]);
+        assert_eq!(analysis.diagnostics, []);
+    }
+
+    #[test]
+    fn unbalanced_brackets_are_reported() {
+        let analysis = analyze(&chars("]'][[a]"));
+        let diagnostics = analysis
+            .diagnostics
+            .iter()
+            .map(|d| (d.span.start, d.severity))
+            .collect::<Vec<_>>();
+
+        assert_eq!(analysis.pairs, [(4, 6)]);
+        assert_eq!(diagnostics, [(0, Severity::Hint), (3, Severity::Error)]);
+    }
+
+    #[test]
+    fn trailing_quote_is_reported() {
+        let analysis = analyze(&chars("'a'"));
+
+        assert_eq!(analysis.diagnostics.len(), 1);
+        assert_eq!(analysis.diagnostics[0].span, 2..3);
+    }
+
+    #[test]
+    fn hover_roles() {
+        let program = chars("v'v[v] x");
+        let text = |offset| hover(&program, offset).map(|h| h.text);
+
+        assert_eq!(
+            text(0).unwrap(),
+            "`v` (Reify): Push the current interpreter."
+        );
+        assert_eq!(text(2).unwrap(), "Pushes 'v'.");
+        assert_eq!(text(4).unwrap(), "Part of a string, pushes 'v'.");
+        assert_eq!(text(6), None);
+        assert_eq!(text(7).unwrap(), "`x` does nothing initially.");
+    }
+}
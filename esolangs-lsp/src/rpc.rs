@@ -0,0 +1,47 @@
+//! Reading and writing JSON-RPC messages.
+
+use std::io::{self, BufRead, Write};
+
+use esolang_core::framing::{read_frame, write_frame};
+use serde_json::Value;
+
+/// Read the next message, or `None` if the input has ended.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    match read_frame(reader)? {
+        Some(body) => serde_json::from_slice(&body)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        None => Ok(None),
+    }
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    write_frame(writer, message.to_string().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let message = json!({ "jsonrpc": "2.0", "method": "exit", "params": "é" });
+
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &message).unwrap();
+
+        let mut reader = &buffer[..];
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_json() {
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, b"{").unwrap();
+
+        assert!(read_message(&mut &buffer[..]).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! Handling messages from the client, independently of how they're transported.
+
+use std::{collections::HashMap, ops::Range};
+
+use serde_json::{json, Value};
+
+use crate::document::{Document, Language};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Debug, Default)]
+pub struct Server {
+    /// Open documents by URI. Documents in languages we don't know are left out.
+    documents: HashMap<String, Document>,
+    shutting_down: bool,
+    exit_code: Option<i32>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Once the client has told us to exit, the code to exit with.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Handle a message, returning the messages to send back.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        match message.get("id") {
+            Some(id) => vec![match self.request(method, params) {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": code, "message": message },
+                }),
+            }],
+            None => self.notification(method, params),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    // Full document sync.
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "documentHighlightProvider": true,
+                },
+                "serverInfo": { "name": "esolangs-lsp" },
+            })),
+            "shutdown" => {
+                self.shutting_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let (document, offset) = match self.locate(params)? {
+                    Some(located) => located,
+                    None => return Ok(Value::Null),
+                };
+
+                Ok(match document.hover(offset) {
+                    Some(hover) => json!({
+                        "contents": { "kind": "markdown", "value": hover.text },
+                        "range": range(document, &hover.span),
+                    }),
+                    None => Value::Null,
+                })
+            }
+            "textDocument/documentHighlight" => {
+                let (document, offset) = match self.locate(params)? {
+                    Some(located) => located,
+                    None => return Ok(Value::Null),
+                };
+
+                Ok(match document.matching_brackets(offset) {
+                    Some((open, close)) => json!([
+                        { "range": range(document, &(open..open + 1)) },
+                        { "range": range(document, &(close..close + 1)) },
+                    ]),
+                    None => Value::Null,
+                })
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unsupported method {}", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned();
+
+        match method {
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                let language =
+                    Language::detect(&uri, document["languageId"].as_str().unwrap_or_default());
+                let text = document["text"].as_str().unwrap_or_default();
+
+                match language {
+                    Some(language) => self.update(uri, language, text),
+                    None => Vec::new(),
+                }
+            }
+            "textDocument/didChange" => {
+                let language = self.documents.get(&uri).map(|doc| doc.language);
+                // With full sync, the last change is the whole document.
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+
+                match (language, text) {
+                    (Some(language), Some(text)) => self.update(uri, language, text),
+                    _ => Vec::new(),
+                }
+            }
+            "textDocument/didClose" => match self.documents.remove(&uri) {
+                Some(_) => vec![publish_diagnostics(&uri, Vec::new())],
+                None => Vec::new(),
+            },
+            "exit" => {
+                self.exit_code = Some(if self.shutting_down { 0 } else { 1 });
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn update(&mut self, uri: String, language: Language, text: &str) -> Vec<Value> {
+        let document = Document::new(language, text);
+        let diagnostics = document
+            .analysis()
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                json!({
+                    "range": range(&document, &diagnostic.span),
+                    "severity": diagnostic.severity as u8,
+                    "source": "esolangs",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect();
+
+        let message = publish_diagnostics(&uri, diagnostics);
+        self.documents.insert(uri, document);
+        vec![message]
+    }
+
+    /// The document and offset that `TextDocumentPositionParams` point at, if we know
+    /// the document and there's a character there.
+    fn locate(&self, params: &Value) -> Result<Option<(&Document, usize)>, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str();
+        let line = params["position"]["line"].as_u64();
+        let column = params["position"]["character"].as_u64();
+
+        let (uri, line, column) = match (uri, line, column) {
+            (Some(uri), Some(line), Some(column)) => (uri, line as u32, column as u32),
+            _ => {
+                return Err((
+                    INVALID_PARAMS,
+                    "expected a document and position".to_owned(),
+                ))
+            }
+        };
+
+        Ok(self.documents.get(uri).and_then(|document| {
+            document
+                .offset(line, column)
+                .map(|offset| (document, offset))
+        }))
+    }
+}
+
+fn range(document: &Document, span: &Range<usize>) -> Value {
+    let (start_line, start_column) = document.position(span.start);
+    let (end_line, end_column) = document.position(span.end);
+    json!({
+        "start": { "line": start_line, "character": start_column },
+        "end": { "line": end_line, "character": end_column },
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(server: &mut Server, uri: &str, text: &str) -> Vec<Value> {
+        server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": { "uri": uri, "languageId": "", "version": 1, "text": text },
+            },
+        }))
+    }
+
+    fn at(server: &mut Server, method: &str, uri: &str, line: u32, column: u32) -> Value {
+        let mut responses = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": column },
+            },
+        }));
+        responses.remove(0)["result"].take()
+    }
+
+    #[test]
+    fn diagnostics_are_published_on_open() {
+        let mut server = Server::new();
+
+        let messages = open(&mut server, "file:///a.mascarpone", "'a.\n[b");
+        let diagnostics = &messages[0]["params"]["diagnostics"];
+
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({ "line": 1, "character": 0 })
+        );
+        assert_eq!(diagnostics[0]["severity"], 1);
+
+        assert_eq!(
+            open(&mut server, "file:///a.txt", "[b"),
+            Vec::<Value>::new()
+        );
+    }
+
+    #[test]
+    fn hover_and_highlight() {
+        let mut server = Server::new();
+        open(&mut server, "file:///a.emm", "#65.");
+        open(&mut server, "file:///a.mascarpone", "[a]");
+
+        let hover = at(&mut server, "textDocument/hover", "file:///a.emm", 0, 1);
+        assert_eq!(hover["contents"]["value"], "Pushes 65 (`'A'`).");
+        assert_eq!(hover["range"]["end"]["character"], 3);
+
+        let highlights = at(
+            &mut server,
+            "textDocument/documentHighlight",
+            "file:///a.mascarpone",
+            0,
+            2,
+        );
+        assert_eq!(highlights[0]["range"]["start"]["character"], 0);
+        assert_eq!(highlights[1]["range"]["start"]["character"], 2);
+
+        let nothing = at(&mut server, "textDocument/hover", "file:///b.emm", 0, 0);
+        assert_eq!(nothing, Value::Null);
+    }
+
+    #[test]
+    fn shutdown_then_exit() {
+        let mut server = Server::new();
+
+        let responses = server.handle(&json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }));
+        assert_eq!(responses[0]["result"], Value::Null);
+        assert_eq!(server.exit_code(), None);
+
+        server.handle(&json!({ "jsonrpc": "2.0", "method": "exit" }));
+        assert_eq!(server.exit_code(), Some(0));
+    }
+
+    #[test]
+    fn unknown_request() {
+        let mut server = Server::new();
+        let responses = server.handle(&json!({ "jsonrpc": "2.0", "id": 1, "method": "foo" }));
+
+        assert_eq!(responses[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,242 @@
+//! Python bindings for Emmental and Mascarpone. Build and install them into the
+//! current virtualenv by running `maturin develop` in this directory.
+//!
+//! ```python
+//! import esolangs
+//!
+//! esolangs.mascarpone_compute("[olleh].......")
+//!
+//! session = esolangs.EmmentalSession(b"#65#66..")
+//! while session.step():
+//!     print(session.stack)
+//! ```
+
+use std::io::Cursor;
+
+use emmental::language::Emmental;
+use esolang_core::{
+    io::InputOutputPair,
+    language::{Execution, Language},
+};
+use mascarpone::{language::Mascarpone, state::Element};
+use pyo3::{create_exception, exceptions::PyException, prelude::*, types::PyBytes};
+
+create_exception!(
+    esolangs,
+    EsolangError,
+    PyException,
+    "Raised when a program fails, with a description of the error as its message."
+);
+
+type IO = InputOutputPair<Cursor<Vec<u8>>, Vec<u8>>;
+
+fn io(input: &[u8]) -> IO {
+    InputOutputPair::new(Cursor::new(input.to_vec()), Vec::new())
+}
+
+fn error<L: Language>(language: &L, error: &L::Error) -> PyErr {
+    EsolangError::new_err(language.report(error).trim_end().to_owned())
+}
+
+/// Run an Emmental program on `input`, returning its output.
+#[pyfunction]
+#[pyo3(signature = (program, input = None))]
+fn emmental_run<'py>(
+    py: Python<'py>,
+    program: &[u8],
+    input: Option<&[u8]>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let output = emmental::run_with_input(program, input.unwrap_or_default())
+        .map_err(|e| error(&Emmental::default(), &e))?;
+    Ok(PyBytes::new(py, &output))
+}
+
+/// Run a Mascarpone program on `input`, returning its output.
+#[pyfunction]
+#[pyo3(signature = (program, input = ""))]
+fn mascarpone_compute(program: &str, input: &str) -> PyResult<String> {
+    mascarpone::compute(program, input).map_err(|e| error(&Mascarpone::default(), &e))
+}
+
+/// Take a step of `execution`, unless it's `done`, which it is once it has finished
+/// or failed.
+fn step<L: Language, E: Execution<Error = L::Error>>(
+    language: &L,
+    execution: &mut E,
+    done: &mut bool,
+) -> PyResult<bool> {
+    if *done {
+        return Ok(false);
+    }
+
+    let result = execution.step();
+    *done = !matches!(result, Ok(true));
+    result.map_err(|e| error(language, &e))
+}
+
+/// An Emmental program being run, one symbol at a time, including the symbols of
+/// the programs that operations run.
+#[pyclass(module = "esolangs")]
+pub struct EmmentalSession {
+    execution: emmental::language::Run<Cursor<Vec<u8>>, Vec<u8>>,
+    done: bool,
+}
+
+#[pymethods]
+impl EmmentalSession {
+    #[new]
+    #[pyo3(signature = (program, input = None))]
+    fn new(program: &[u8], input: Option<&[u8]>) -> PyResult<Self> {
+        let emmental = Emmental::default();
+        let program = emmental.parse(program).map_err(|e| error(&emmental, &e))?;
+        Ok(Self {
+            execution: emmental.start(program, io(input.unwrap_or_default())),
+            done: false,
+        })
+    }
+
+    /// Interpret the next symbol, returning `False` if the program is done.
+    fn step(&mut self) -> PyResult<bool> {
+        step(&Emmental::default(), &mut self.execution, &mut self.done)
+    }
+
+    /// Run the rest of the program.
+    fn run(&mut self) -> PyResult<()> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    /// The symbols on the stack, from the bottom up, as `bytes`.
+    #[getter]
+    fn stack(&self) -> Vec<u8> {
+        self.execution.state().stack().iter().copied().collect()
+    }
+
+    /// The symbols in the queue, from the front to the back, as `bytes`.
+    #[getter]
+    fn queue(&self) -> Vec<u8> {
+        self.execution.state().queue().iter().copied().collect()
+    }
+
+    /// Everything the program has output so far.
+    #[getter]
+    fn output<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.execution.state().io.output)
+    }
+}
+
+/// A Mascarpone program being run, one symbol at a time, including the symbols of
+/// performed operations.
+#[pyclass(module = "esolangs")]
+pub struct MascarponeSession {
+    execution: mascarpone::language::Run<Cursor<Vec<u8>>, Vec<u8>>,
+    done: bool,
+}
+
+#[pymethods]
+impl MascarponeSession {
+    #[new]
+    #[pyo3(signature = (program, input = ""))]
+    fn new(program: &str, input: &str) -> PyResult<Self> {
+        let language = Mascarpone::default();
+        let program = language
+            .parse(program.as_bytes())
+            .map_err(|e| error(&language, &e))?;
+        Ok(Self {
+            execution: language.start(program, io(input.as_bytes())),
+            done: false,
+        })
+    }
+
+    /// Interpret the next symbol, returning `False` if the program is done.
+    fn step(&mut self) -> PyResult<bool> {
+        step(&Mascarpone::default(), &mut self.execution, &mut self.done)
+    }
+
+    /// Run the rest of the program.
+    fn run(&mut self) -> PyResult<()> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    /// The elements on the stack, from the bottom up, as `(kind, value)` pairs. The
+    /// value of a symbol is the symbol itself, other elements are described.
+    #[getter]
+    fn stack(&self) -> Vec<(String, String)> {
+        let mut stack = self
+            .execution
+            .state()
+            .stack()
+            .map(|elem| {
+                let value = match &*elem {
+                    Element::Symbol(sym) => sym.to_string(),
+                    other => other.to_string(),
+                };
+                (elem.kind().to_string(), value)
+            })
+            .collect::<Vec<_>>();
+        stack.reverse();
+        stack
+    }
+
+    /// The number of programs being executed, see `State::call_depth`.
+    #[getter]
+    fn call_depth(&self) -> usize {
+        self.execution.state().call_depth()
+    }
+
+    /// Everything the program has output so far.
+    #[getter]
+    fn output(&self) -> String {
+        let io = self.execution.state().io().get_ref();
+        String::from_utf8_lossy(&io.output).into_owned()
+    }
+}
+
+#[pymodule]
+fn esolangs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(emmental_run, m)?)?;
+    m.add_function(wrap_pyfunction!(mascarpone_compute, m)?)?;
+    m.add_class::<EmmentalSession>()?;
+    m.add_class::<MascarponeSession>()?;
+    m.add("EsolangError", m.py().get_type::<EsolangError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emmental_session_steps() {
+        let mut session = EmmentalSession::new(b"#65#66;..", None).unwrap();
+
+        for _ in 0..3 {
+            assert!(session.step().unwrap());
+        }
+        assert_eq!(session.stack(), [65]);
+
+        session.run().unwrap();
+        assert!(!session.step().unwrap());
+        assert_eq!(session.execution.state().io.output, b";B");
+    }
+
+    #[test]
+    fn mascarpone_session_steps() {
+        let mut session = MascarponeSession::new("'a[.]v*", "").unwrap();
+
+        session.run().unwrap();
+        assert_eq!(session.stack()[0], ("symbol".to_owned(), "a".to_owned()));
+        assert_eq!(session.stack()[1].0, "operation");
+        assert_eq!(session.call_depth(), 0);
+    }
+
+    #[test]
+    fn session_is_done_after_error() {
+        let mut session = MascarponeSession::new(".'a.", "").unwrap();
+
+        assert!(session.step().is_err());
+        assert!(!session.step().unwrap());
+        assert_eq!(session.output(), "");
+    }
+}
@@ -0,0 +1,508 @@
+//! Golden-file tests: programs that are run with known input, and whose output is
+//! compared with what was expected.
+//!
+//! A test is a program in any of the languages, e.g. `hello.emm`, along with files
+//! next to it with the same stem:
+//!
+//! - `hello.in`, the input, which is empty if the file is missing;
+//! - `hello.out`, the output the program should give;
+//! - `hello.err`, the error report, if the program should fail.
+//!
+//! Blessing a test rewrites these files to match what the program actually does.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use esolang_core::io::SharedOutput;
+
+use crate::{languages, select, Input};
+
+/// How many symbols a program may interpret by default.
+pub const DEFAULT_BUDGET: u64 = 1_000_000;
+
+/// How large the table comparing every differing line with every other may get.
+const MAX_DIFF_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// How many symbols a program may interpret before it counts as failed.
+    pub budget: u64,
+    /// Rewrite the expected files instead of comparing with them.
+    pub bless: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            budget: DEFAULT_BUDGET,
+            bless: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// Some of the expected files were rewritten.
+    Blessed,
+    /// What went wrong, with diffs.
+    Failed(String),
+}
+
+/// The outcomes of tests, which show the failures and a summary when displayed.
+#[derive(Debug, Clone, Default)]
+pub struct Results {
+    root: PathBuf,
+    pub outcomes: Vec<(PathBuf, Outcome)>,
+}
+
+impl Results {
+    /// Results for the tests in `root`. Programs are shown relative to it.
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_owned(),
+            outcomes: Vec::new(),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        !self
+            .outcomes
+            .iter()
+            .any(|(_, outcome)| matches!(outcome, Outcome::Failed(_)))
+    }
+
+    /// How to show `program` to the user.
+    pub fn name<'a>(&self, program: &'a Path) -> impl fmt::Display + 'a {
+        program
+            .strip_prefix(&self.root)
+            .unwrap_or(program)
+            .display()
+    }
+}
+
+impl fmt::Display for Results {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = |f: fn(&Outcome) -> bool| {
+            self.outcomes
+                .iter()
+                .filter(|(_, outcome)| f(outcome))
+                .count()
+        };
+
+        let failures = self
+            .outcomes
+            .iter()
+            .filter_map(|(program, outcome)| match outcome {
+                Outcome::Failed(message) => Some((program, message)),
+                _ => None,
+            });
+        let mut failures = failures.peekable();
+        if failures.peek().is_some() {
+            writeln!(f, "failures:")?;
+            for (program, message) in failures {
+                write!(f, "\n---- {} ----\n{}", self.name(program), message)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(
+            f,
+            "test result: {}. {} passed; {} failed; {} blessed",
+            if self.passed() { "ok" } else { "FAILED" },
+            count(|outcome| *outcome == Outcome::Passed),
+            count(|outcome| matches!(outcome, Outcome::Failed(_))),
+            count(|outcome| *outcome == Outcome::Blessed),
+        )
+    }
+}
+
+/// Run all the tests in `dir` and its subdirectories.
+pub fn run(dir: &Path, options: &Options) -> io::Result<Results> {
+    let mut results = Results::new(dir);
+    for program in discover(dir)? {
+        let outcome = run_test(&program, options)?;
+        results.outcomes.push((program, outcome));
+    }
+    Ok(results)
+}
+
+/// The programs in `dir` and its subdirectories, in order, recognized by the
+/// extensions of the languages.
+pub fn discover(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let languages = languages();
+    let mut programs = Vec::new();
+    let mut dirs = vec![dir.to_owned()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if let Some(path_str) = path.to_str() {
+                if select(&languages, None, path_str).is_ok() {
+                    programs.push(path);
+                }
+            }
+        }
+    }
+
+    programs.sort();
+    Ok(programs)
+}
+
+/// Run the test of `program`, blessing it if the options say so.
+pub fn run_test(program: &Path, options: &Options) -> io::Result<Outcome> {
+    let languages = languages();
+    let language = program
+        .to_str()
+        .ok_or("path is not valid UTF-8")
+        .and_then(|path| select(&languages, None, path).map_err(|_| "unknown language"))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let source = fs::read(program)?;
+    let input = read_optional(&program.with_extension("in"))?.unwrap_or_default();
+    let output = SharedOutput::default();
+
+    let mut error = None;
+    match language.start(&source, Input::new(input), output.clone()) {
+        Ok(mut execution) => {
+            let mut steps = 0;
+            loop {
+                if steps == options.budget {
+                    error = Some(format!(
+                        "error: did not finish within {} steps\n",
+                        options.budget
+                    ));
+                    break;
+                }
+
+                match execution.step() {
+                    Ok(true) => steps += 1,
+                    Ok(false) => break,
+                    Err(report) => {
+                        error = Some(report);
+                        break;
+                    }
+                }
+            }
+        }
+        Err(report) => error = Some(report),
+    }
+    let output = output.contents();
+    let error = error.map(String::into_bytes);
+
+    let out_path = program.with_extension("out");
+    let err_path = program.with_extension("err");
+    let expected_output = read_optional(&out_path)?;
+    let expected_error = read_optional(&err_path)?;
+
+    if options.bless {
+        let mut changed = false;
+        if expected_output.as_ref() != Some(&output) {
+            fs::write(&out_path, &output)?;
+            changed = true;
+        }
+        match &error {
+            Some(error) if expected_error.as_ref() != Some(error) => {
+                fs::write(&err_path, error)?;
+                changed = true;
+            }
+            None if expected_error.is_some() => {
+                fs::remove_file(&err_path)?;
+                changed = true;
+            }
+            _ => (),
+        }
+
+        return Ok(if changed {
+            Outcome::Blessed
+        } else {
+            Outcome::Passed
+        });
+    }
+
+    let mut problems = Vec::new();
+    match expected_output {
+        None => problems.push(format!(
+            "{} is missing, bless the test to create it\n",
+            out_path.display()
+        )),
+        Some(expected) if expected != output => problems.push(format!(
+            "output differs from {}:\n{}",
+            out_path.display(),
+            diff(&expected, &output)
+        )),
+        Some(_) => (),
+    }
+    if expected_error != error {
+        let expected = expected_error.unwrap_or_default();
+        let actual = error.unwrap_or_default();
+        problems.push(format!(
+            "error report differs from {}:\n{}",
+            err_path.display(),
+            diff(&expected, &actual)
+        ));
+    }
+
+    Ok(if problems.is_empty() {
+        Outcome::Passed
+    } else {
+        Outcome::Failed(problems.join("\n"))
+    })
+}
+
+fn read_optional(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// A line-by-line diff of `expected` and `actual`, with `-` for lines that are only
+/// expected and `+` for lines that only turned up. If too many lines differ to
+/// compare them all with each other, only the first difference is shown.
+fn diff(expected: &[u8], actual: &[u8]) -> String {
+    let expected = String::from_utf8_lossy(expected);
+    let actual = String::from_utf8_lossy(actual);
+    let expected = expected.split_inclusive('\n').collect::<Vec<_>>();
+    let actual = actual.split_inclusive('\n').collect::<Vec<_>>();
+
+    // Lines that both start or end with don't need comparing.
+    let prefix = expected
+        .iter()
+        .zip(&actual)
+        .take_while(|(e, a)| e == a)
+        .count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let (common_start, common_end) = (&expected[..prefix], &expected[expected.len() - suffix..]);
+    let expected = &expected[prefix..expected.len() - suffix];
+    let actual = &actual[prefix..actual.len() - suffix];
+
+    if (expected.len() + 1).saturating_mul(actual.len() + 1) > MAX_DIFF_SIZE {
+        let mut lines = format!(
+            "(too many lines differ to compare, showing the first difference, on line {})\n",
+            prefix + 1
+        );
+        if let Some(line) = expected.first() {
+            push_line(&mut lines, '-', line);
+        }
+        if let Some(line) = actual.first() {
+            push_line(&mut lines, '+', line);
+        }
+        return lines;
+    }
+
+    // The lengths of the longest common subsequences of the ends of both.
+    let mut lengths = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = String::new();
+    let mut push = |prefix: char, line: &str| push_line(&mut lines, prefix, line);
+    for line in common_start {
+        push(' ', line);
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            push(' ', expected[i]);
+            i += 1;
+            j += 1;
+        } else if i < expected.len()
+            && (j == actual.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            push('-', expected[i]);
+            i += 1;
+        } else {
+            push('+', actual[j]);
+            j += 1;
+        }
+    }
+    for line in common_end {
+        push(' ', line);
+    }
+    lines
+}
+
+fn push_line(lines: &mut String, prefix: char, line: &str) {
+    lines.push(prefix);
+    match line.strip_suffix('\n') {
+        Some(line) => lines.push_str(&line.escape_debug().to_string()),
+        None => {
+            lines.push_str(&line.escape_debug().to_string());
+            lines.push_str(" (no newline at end)");
+        }
+    }
+    lines.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = env::temp_dir().join(format!("esolangs-golden-{}-{}", process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            for (file, contents) in files {
+                let path = dir.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            Self(dir)
+        }
+
+        fn read(&self, file: &str) -> Option<String> {
+            fs::read_to_string(self.0.join(file)).ok()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn discover_programs() {
+        let dir = TempDir::new(
+            "discover",
+            &[
+                ("b.emm", ""),
+                ("a/c.mascarpone", ""),
+                ("b.out", ""),
+                ("notes.txt", ""),
+            ],
+        );
+
+        let programs = discover(&dir.0).unwrap();
+        assert_eq!(
+            programs,
+            [dir.0.join("a/c.mascarpone"), dir.0.join("b.emm")]
+        );
+    }
+
+    #[test]
+    fn compare_with_expected_files() {
+        let dir = TempDir::new(
+            "compare",
+            &[
+                ("echo.emm", ",.,."),
+                ("echo.in", "hi"),
+                ("echo.out", "hi"),
+                ("wrong.mascarpone", "'a.'b."),
+                ("wrong.out", "ac"),
+                ("fails.emm", "+"),
+                ("fails.out", ""),
+                ("missing.emm", ""),
+            ],
+        );
+
+        let results = run(&dir.0, &Options::default()).unwrap();
+        let outcome = |name: &str| {
+            let program = dir.0.join(name);
+            let (_, outcome) = results
+                .outcomes
+                .iter()
+                .find(|(p, _)| *p == program)
+                .unwrap();
+            outcome.clone()
+        };
+
+        assert!(!results.passed());
+        assert_eq!(outcome("echo.emm"), Outcome::Passed);
+        assert_eq!(
+            outcome("wrong.mascarpone"),
+            Outcome::Failed(format!(
+                "output differs from {}:\n-ac (no newline at end)\n+ab (no newline at end)\n",
+                dir.0.join("wrong.out").display()
+            ))
+        );
+        assert!(matches!(outcome("fails.emm"), Outcome::Failed(m) if m.contains("+error: ")));
+        assert!(matches!(outcome("missing.emm"), Outcome::Failed(m) if m.contains("missing")));
+        assert!(results
+            .to_string()
+            .ends_with("test result: FAILED. 1 passed; 3 failed; 0 blessed\n"));
+    }
+
+    #[test]
+    fn bless_then_pass() {
+        let dir = TempDir::new(
+            "bless",
+            &[
+                ("loop.mascarpone", "[:!]v*:!"),
+                ("fixed.emm", "#65."),
+                ("fixed.err", "error: stale\n"),
+            ],
+        );
+        let bless = Options {
+            budget: 1000,
+            bless: true,
+        };
+
+        let results = run(&dir.0, &bless).unwrap();
+        assert!(results.outcomes.iter().all(|(_, o)| *o == Outcome::Blessed));
+        assert_eq!(dir.read("fixed.out").as_deref(), Some("A"));
+        assert_eq!(dir.read("fixed.err"), None);
+        assert_eq!(
+            dir.read("loop.err").as_deref(),
+            Some("error: did not finish within 1000 steps\n")
+        );
+
+        let results = run(
+            &dir.0,
+            &Options {
+                bless: false,
+                ..bless
+            },
+        )
+        .unwrap();
+        assert!(results.passed(), "{}", results);
+        let results = run(&dir.0, &bless).unwrap();
+        assert!(results.outcomes.iter().all(|(_, o)| *o == Outcome::Passed));
+    }
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!(diff(b"a\nb\nc\n", b"a\nc\nd\n"), " a\n-b\n c\n+d\n");
+        assert_eq!(diff(b"\x01\n", b""), "-\\u{1}\n");
+    }
+
+    #[test]
+    fn diff_too_many_lines() {
+        let lines = |prefix: &str| {
+            let mut lines = "same\n".to_owned();
+            for i in 0..2000 {
+                lines.push_str(&format!("{}{}\n", prefix, i));
+            }
+            lines
+        };
+
+        assert_eq!(
+            diff(lines("e").as_bytes(), lines("a").as_bytes()),
+            "(too many lines differ to compare, showing the first difference, on line 2)\n\
+             -e0\n\
+             +a0\n"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Running programs in any of the languages in this workspace, picking the
+//! language at runtime.
+
+pub mod golden;
+pub mod visualizer;
+
+use std::{
+    cell::Cell,
+    io::{self, Read, Stdin, Stdout},
+    path::Path,
+    rc::Rc,
+};
+
+use emmental::language::Emmental;
+use esolang_core::{
+    io::{InputOutputPair, SharedOutput},
+    language::{AnyExecution, Execution, Language, Started},
+};
+use mascarpone::language::Mascarpone;
+
+/// Input that is all known up front, keeping track of how much has been read.
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    data: Rc<Vec<u8>>,
+    read: Rc<Cell<usize>>,
+}
+
+impl Input {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data: Rc::new(data),
+            read: Rc::default(),
+        }
+    }
+
+    /// All of the input, and how much of it has been read.
+    pub fn progress(&self) -> (&[u8], usize) {
+        (&self.data, self.read.get())
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut rest = &self.data[self.read.get()..];
+        let count = rest.read(buf)?;
+        self.read.set(self.read.get() + count);
+        Ok(count)
+    }
+}
+
+/// The part of `Language` we need here, without the associated types, so
+/// languages can be picked at runtime.
+pub trait Runner {
+    fn name(&self) -> &'static str;
+    fn extensions(&self) -> &'static [&'static str];
+    /// Run `source` using standard input and output. Errors are reported as they
+    /// should be shown to the user.
+    fn run(&self, source: &[u8], io: InputOutputPair<Stdin, Stdout>) -> Result<(), String>;
+    /// Start running `source`, to be stepped through by the caller.
+    fn start(
+        &self,
+        source: &[u8],
+        input: Input,
+        output: SharedOutput,
+    ) -> Result<Box<dyn AnyExecution>, String>;
+}
+
+impl<L: Language + Clone + 'static> Runner for L {
+    fn name(&self) -> &'static str {
+        Language::name(self)
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        Language::extensions(self)
+    }
+
+    fn run(&self, source: &[u8], io: InputOutputPair<Stdin, Stdout>) -> Result<(), String> {
+        let program = self.parse(source).map_err(|e| self.report(&e))?;
+        Language::start(self, program, io)
+            .run()
+            .map_err(|e| self.report(&e))
+    }
+
+    fn start(
+        &self,
+        source: &[u8],
+        input: Input,
+        output: SharedOutput,
+    ) -> Result<Box<dyn AnyExecution>, String> {
+        let io = InputOutputPair::new(input, output);
+        Ok(Box::new(Started::new(self.clone(), source, io)?))
+    }
+}
+
+pub fn languages() -> Vec<Box<dyn Runner>> {
+    vec![
+        Box::new(Emmental::default()),
+        Box::new(Mascarpone::default()),
+    ]
+}
+
+/// Pick the language called `name`, or else the one that `path` has an extension
+/// of.
+pub fn select<'a>(
+    languages: &'a [Box<dyn Runner>],
+    name: Option<&str>,
+    path: &str,
+) -> Result<&'a dyn Runner, String> {
+    let found = match name {
+        Some(name) => languages.iter().find(|lang| lang.name() == name),
+        None => {
+            let extension = Path::new(path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .ok_or_else(|| {
+                    format!(
+                        "error: can't tell the language of {}, use --lang to specify it",
+                        path
+                    )
+                })?;
+            languages
+                .iter()
+                .find(|lang| lang.extensions().contains(&extension))
+        }
+    };
+
+    found.map(|lang| &**lang).ok_or_else(|| {
+        let names = languages.iter().map(|lang| lang.name()).collect::<Vec<_>>();
+        match name {
+            Some(name) => format!(
+                "error: unknown language {:?}, expected one of: {}",
+                name,
+                names.join(", ")
+            ),
+            None => format!(
+                "error: unknown extension on {}, use --lang to specify one of: {}",
+                path,
+                names.join(", ")
+            ),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(name: Option<&str>, path: &str) -> Result<&'static str, String> {
+        select(&languages(), name, path).map(|lang| lang.name())
+    }
+
+    #[test]
+    fn select_by_extension() {
+        assert_eq!(selected(None, "hello.emm"), Ok("emmental"));
+        assert_eq!(selected(None, "dir.emm/hello.mascarpone"), Ok("mascarpone"));
+    }
+
+    #[test]
+    fn select_by_name_overrides_extension() {
+        assert_eq!(selected(Some("mascarpone"), "hello.emm"), Ok("mascarpone"));
+        assert_eq!(selected(Some("emmental"), "hello"), Ok("emmental"));
+    }
+
+    #[test]
+    fn select_unknown() {
+        assert!(selected(None, "hello").is_err());
+        assert!(selected(None, "hello.txt").is_err());
+        assert!(selected(Some("piet"), "hello.emm").is_err());
+    }
+}
@@ -0,0 +1,131 @@
+mod tui;
+
+use std::{env, fs, io, path::Path, process};
+
+use esolang_core::io::{InputOutputPair, SharedOutput};
+use esolangs_runner::{
+    golden::{self, Options, Outcome, Results},
+    languages, select,
+    visualizer::Visualizer,
+    Input, Runner,
+};
+
+const USAGE: &str = "usage: esolangs [--lang <language>] [--tui [--input <file>]] <program>
+       esolangs test [--bless] [--budget <steps>] <dir>";
+
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("test") {
+        args.next();
+        return test(args);
+    }
+
+    let mut name = None;
+    let mut path = None;
+    let mut tui = false;
+    let mut input_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lang" => name = Some(args.next().unwrap_or_else(|| fail(USAGE))),
+            "--tui" => tui = true,
+            "--input" => input_path = Some(args.next().unwrap_or_else(|| fail(USAGE))),
+            _ if path.is_none() => path = Some(arg),
+            _ => fail(USAGE),
+        }
+    }
+
+    let path = path.unwrap_or_else(|| fail(USAGE));
+    if input_path.is_some() && !tui {
+        fail(USAGE);
+    }
+
+    let languages = languages();
+    let language = select(&languages, name.as_deref(), &path).unwrap_or_else(|e| fail(&e));
+    let source =
+        fs::read(&path).unwrap_or_else(|e| fail(&format!("error: could not read {}: {}", path, e)));
+
+    if tui {
+        let input = match input_path {
+            Some(input_path) => fs::read(&input_path)
+                .unwrap_or_else(|e| fail(&format!("error: could not read {}: {}", input_path, e))),
+            None => Vec::new(),
+        };
+        visualize(language, &path, &source, input);
+    } else if let Err(report) = language.run(&source, InputOutputPair::standard()) {
+        eprint!("{}", report);
+        process::exit(1);
+    }
+}
+
+fn test(mut args: impl Iterator<Item = String>) {
+    let mut options = Options::default();
+    let mut dir = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bless" => options.bless = true,
+            "--budget" => {
+                options.budget = args
+                    .next()
+                    .and_then(|budget| budget.parse().ok())
+                    .unwrap_or_else(|| fail(USAGE))
+            }
+            _ if dir.is_none() => dir = Some(arg),
+            _ => fail(USAGE),
+        }
+    }
+
+    let dir = dir.unwrap_or_else(|| fail(USAGE));
+    let dir = Path::new(&dir);
+    let io_error = |e: io::Error| -> ! {
+        fail(&format!(
+            "error: could not run tests in {}: {}",
+            dir.display(),
+            e
+        ))
+    };
+
+    let programs = golden::discover(dir).unwrap_or_else(|e| io_error(e));
+    println!("running {} tests", programs.len());
+
+    let mut results = Results::new(dir);
+    for program in programs {
+        let outcome = golden::run_test(&program, &options).unwrap_or_else(|e| io_error(e));
+        let status = match outcome {
+            Outcome::Passed => "ok",
+            Outcome::Blessed => "blessed",
+            Outcome::Failed(_) => "FAILED",
+        };
+        println!("test {} ... {}", results.name(&program), status);
+        results.outcomes.push((program, outcome));
+    }
+
+    println!();
+    print!("{}", results);
+    if !results.passed() {
+        process::exit(1);
+    }
+}
+
+fn visualize(language: &dyn Runner, path: &str, source: &[u8], input: Vec<u8>) {
+    let input = Input::new(input);
+    let output = SharedOutput::default();
+    let execution = language
+        .start(source, input.clone(), output.clone())
+        .unwrap_or_else(|report| {
+            eprint!("{}", report);
+            process::exit(1);
+        });
+
+    let mut visualizer = Visualizer::new(execution, input, output);
+    let title = format!("{} ({})", path, language.name());
+    if let Err(e) = tui::show(&mut visualizer, &title) {
+        fail(&format!("error: could not use the terminal: {}", e));
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
@@ -0,0 +1,372 @@
+//! Showing a `Visualizer` full screen, and controlling it with the keyboard.
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, List, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+use esolangs_runner::visualizer::{Status, Visualizer};
+
+/// How often the screen is redrawn while the program runs.
+const FRAME_RATE: u32 = 30;
+
+const KEYS: &str =
+    "space step · r run/pause · c run to breakpoint · arrows move · b breakpoint · +/- speed · q quit";
+
+/// Show `visualizer` until the user quits.
+pub fn show(visualizer: &mut Visualizer, title: &str) -> io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = run(&mut terminal, visualizer, title);
+    ratatui::try_restore()?;
+    result
+}
+
+fn run(terminal: &mut DefaultTerminal, visualizer: &mut Visualizer, title: &str) -> io::Result<()> {
+    let mut last_run = Instant::now();
+
+    loop {
+        terminal.draw(|frame| draw(frame, visualizer, title))?;
+
+        // Run in batches, so fast speeds don't mean redrawing all the time.
+        let (period, batch) = match visualizer.status() {
+            Status::Skipping => (Duration::from_secs(1) / FRAME_RATE, usize::MAX),
+            _ if visualizer.speed() > FRAME_RATE => (
+                Duration::from_secs(1) / FRAME_RATE,
+                (visualizer.speed() / FRAME_RATE) as usize,
+            ),
+            _ => (Duration::from_secs(1) / visualizer.speed(), 1),
+        };
+        let is_running = matches!(visualizer.status(), Status::Running | Status::Skipping);
+
+        let timeout = if is_running {
+            period.saturating_sub(last_run.elapsed())
+        } else {
+            Duration::from_secs(60)
+        };
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char(' ') | KeyCode::Char('s') => visualizer.step(),
+                    KeyCode::Char('r') | KeyCode::Enter => {
+                        visualizer.toggle_running();
+                        last_run = Instant::now();
+                    }
+                    KeyCode::Char('c') => visualizer.skip_to_breakpoint(),
+                    KeyCode::Char('b') => visualizer.toggle_breakpoint(),
+                    KeyCode::Char('+') | KeyCode::Char('=') => visualizer.faster(),
+                    KeyCode::Char('-') => visualizer.slower(),
+                    KeyCode::Left | KeyCode::Char('h') => visualizer.move_cursor(-1),
+                    KeyCode::Right | KeyCode::Char('l') => visualizer.move_cursor(1),
+                    KeyCode::Up | KeyCode::Char('k') => visualizer.move_cursor_lines(-1),
+                    KeyCode::Down | KeyCode::Char('j') => visualizer.move_cursor_lines(1),
+                    _ => (),
+                }
+            }
+        } else if is_running {
+            last_run = Instant::now();
+            if batch == usize::MAX {
+                // Keep the screen and keyboard responsive while skipping.
+                let deadline = last_run + period;
+                while Instant::now() < deadline && *visualizer.status() == Status::Skipping {
+                    visualizer.run(1_000);
+                }
+            } else {
+                visualizer.run(batch);
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, visualizer: &Visualizer, title: &str) {
+    let [main, streams, status] = Layout::vertical([
+        Constraint::Min(6),
+        Constraint::Length(6),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [code, state] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+
+    draw_code(frame, visualizer, title, code);
+    draw_scopes(frame, visualizer, state);
+    draw_streams(frame, visualizer, streams);
+
+    let status_text = match visualizer.status() {
+        Status::Paused => "paused".to_owned(),
+        Status::Running => "running".to_owned(),
+        Status::Skipping => "running to breakpoint".to_owned(),
+        Status::Finished => "finished".to_owned(),
+        Status::Failed(report) => report.trim_end().to_owned(),
+    };
+    let status_style = match visualizer.status() {
+        Status::Failed(_) => Style::new().fg(Color::Red),
+        _ => Style::new().bold(),
+    };
+    frame.render_widget(
+        Line::from(vec![
+            Span::styled(status_text, status_style),
+            Span::raw(format!(
+                " · {} steps · {}/s · {}",
+                visualizer.steps(),
+                visualizer.speed(),
+                KEYS
+            )),
+        ]),
+        status,
+    );
+}
+
+/// How the symbol that runs next looks.
+fn next_style() -> Style {
+    Style::new().black().on_yellow()
+}
+
+/// How a symbol that is running an operation looks.
+fn calling_style() -> Style {
+    Style::new().black().on_cyan()
+}
+
+/// Control characters would mess up the layout, so they're shown as dots.
+fn printable(c: char) -> char {
+    if c.is_control() {
+        '·'
+    } else {
+        c
+    }
+}
+
+fn draw_code(frame: &mut Frame, visualizer: &Visualizer, title: &str, area: Rect) {
+    let frames = visualizer.frames();
+    let operations = frames.len().saturating_sub(1);
+    let [program_area, operations_area] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(if operations > 0 {
+            operations.min(8) as u16 + 2
+        } else {
+            0
+        }),
+    ])
+    .areas(area);
+
+    // The symbol to highlight in the program: the next one, or the one running the
+    // operations below.
+    let highlight = match frames.first() {
+        Some(outer) if frames.len() == 1 => Some((outer.offset, next_style())),
+        Some(outer) => Some((outer.offset - 1, calling_style())),
+        None => None,
+    };
+
+    let program = visualizer.program();
+    let mut highlighted_line = 0;
+    let lines = visualizer
+        .lines()
+        .into_iter()
+        .enumerate()
+        .map(|(number, range)| {
+            let spans = range
+                .map(|offset| {
+                    let mut style = Style::new();
+                    if visualizer.breakpoints().contains(&offset) {
+                        style = style.fg(Color::Red).bold();
+                    }
+                    if offset == visualizer.cursor() {
+                        style = style.add_modifier(Modifier::UNDERLINED);
+                    }
+                    if let Some((_, highlight)) = highlight.filter(|&(at, _)| at == offset) {
+                        style = style.patch(highlight);
+                        highlighted_line = number;
+                    }
+
+                    // Newlines are shown as a space, so there's something to
+                    // highlight.
+                    let c = match program[offset] {
+                        '\n' => ' ',
+                        c => printable(c),
+                    };
+                    Span::styled(c.to_string(), style)
+                })
+                .collect::<Vec<_>>();
+            Line::from(spans)
+        })
+        .collect::<Vec<_>>();
+
+    // Keep the highlighted line in view.
+    let height = program_area.height.saturating_sub(2) as usize;
+    let scroll = highlighted_line.saturating_sub(height / 2);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(Block::bordered().title(title.to_owned()))
+            .scroll((scroll as u16, 0)),
+        program_area,
+    );
+
+    if operations > 0 {
+        let lines = frames
+            .iter()
+            .enumerate()
+            .skip(1)
+            .rev()
+            .take(8)
+            .map(|(depth, operation)| {
+                let innermost = depth + 1 == frames.len();
+                let (at, style) = if innermost {
+                    (operation.offset, next_style())
+                } else {
+                    (operation.offset - 1, calling_style())
+                };
+
+                let spans = visualizer
+                    .inspect()
+                    .program(depth)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(offset, c)| {
+                        let style = if offset == at { style } else { Style::new() };
+                        Span::styled(printable(c).to_string(), style)
+                    });
+                Line::from(
+                    std::iter::once(Span::raw(format!("{:>3} ", depth)).dim())
+                        .chain(spans)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        frame.render_widget(
+            Paragraph::new(lines).block(
+                Block::bordered()
+                    .title(format!("Operations ({} deep, innermost first)", operations)),
+            ),
+            operations_area,
+        );
+    }
+}
+
+fn draw_scopes(frame: &mut Frame, visualizer: &Visualizer, area: Rect) {
+    let scopes = visualizer.scopes();
+    let areas = Layout::vertical(scopes.iter().map(|_| Constraint::Fill(1))).split(area);
+
+    for (scope, &area) in scopes.iter().zip(areas.iter()) {
+        let width = scope
+            .entries
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let items = scope.entries.iter().map(|(name, value)| {
+            Line::from(vec![
+                Span::raw(format!("{:>width$} ", name, width = width)).dim(),
+                Span::raw(value.chars().map(printable).collect::<String>()),
+            ])
+        });
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(format!(
+                "{} ({})",
+                scope.name,
+                scope.entries.len()
+            ))),
+            area,
+        );
+    }
+}
+
+fn draw_streams(frame: &mut Frame, visualizer: &Visualizer, area: Rect) {
+    let [input_area, output_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(area);
+
+    // What has been read is dimmed.
+    let (input, read) = visualizer.input().progress();
+    let show = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .chars()
+            .map(|c| if c == '\n' { c } else { printable(c) })
+            .collect::<String>()
+    };
+    let mut text = Text::from(Line::from(Span::raw(show(&input[..read])).dim()));
+    for (i, line) in show(&input[read..]).split('\n').enumerate() {
+        if i == 0 {
+            text.push_span(Span::raw(line.to_owned()));
+        } else {
+            text.push_line(Line::raw(line.to_owned()));
+        }
+    }
+    frame.render_widget(
+        Paragraph::new(text)
+            .block(Block::bordered().title(format!("Input ({}/{} read)", read, input.len())))
+            .wrap(Wrap { trim: false }),
+        input_area,
+    );
+
+    // Keep the end of the output in view.
+    let output = show(&visualizer.output().contents());
+    let height = output_area.height.saturating_sub(2) as usize;
+    let lines = output.split('\n').count();
+    frame.render_widget(
+        Paragraph::new(output)
+            .block(Block::bordered().title("Output"))
+            .wrap(Wrap { trim: false })
+            .scroll((lines.saturating_sub(height) as u16, 0)),
+        output_area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use mascarpone::language::Mascarpone;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+    use esolang_core::io::SharedOutput;
+    use esolangs_runner::{Input, Runner};
+
+    #[test]
+    fn draw_panes() {
+        let input = Input::new(b"in".to_vec());
+        let output = SharedOutput::default();
+        let language = Mascarpone::default();
+        let execution = Runner::start(&language, b"'a'b.v", input.clone(), output.clone()).unwrap();
+        let mut visualizer = Visualizer::new(execution, input, output);
+        for _ in 0..4 {
+            visualizer.step();
+        }
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal
+            .draw(|frame| draw(frame, &visualizer, "test.mascarpone"))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        let screen = (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert!(screen.contains("test.mascarpone"));
+        assert!(screen.contains("Stack (2)"));
+        assert!(screen.contains("'b'"));
+        assert!(screen.contains("<initial interpreter>"));
+        assert!(screen.contains("Input (0/2 read)"));
+        assert!(screen.contains("paused · 4 steps"));
+
+        // The next symbol, `.`, is highlighted.
+        assert_eq!(buffer[(5, 1)].symbol(), ".");
+        assert_eq!(buffer[(5, 1)].bg, Color::Yellow);
+    }
+}
@@ -0,0 +1,285 @@
+//! Watching a program run, step by step or at a steady pace, independently of how
+//! it's shown.
+
+use std::{collections::BTreeSet, ops::Range};
+
+use esolang_core::{
+    io::SharedOutput,
+    language::{AnyExecution, Frame, Inspect, Scope},
+};
+
+use crate::Input;
+
+/// The speeds to choose from, in symbols per second.
+pub const SPEEDS: &[u32] = &[1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000, 10_000, 100_000];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Paused,
+    /// Running at the chosen speed.
+    Running,
+    /// Running as fast as possible, until a breakpoint.
+    Skipping,
+    Finished,
+    /// The report of the error the program ran into.
+    Failed(String),
+}
+
+pub struct Visualizer {
+    execution: Box<dyn AnyExecution>,
+    input: Input,
+    output: SharedOutput,
+    /// The program that was started, which breakpoints and the cursor are in.
+    program: Vec<char>,
+    breakpoints: BTreeSet<usize>,
+    cursor: usize,
+    status: Status,
+    speed: usize,
+    steps: u64,
+}
+
+impl Visualizer {
+    pub fn new(execution: Box<dyn AnyExecution>, input: Input, output: SharedOutput) -> Self {
+        let program = execution.inspect().program(0);
+        Self {
+            execution,
+            input,
+            output,
+            program,
+            breakpoints: BTreeSet::new(),
+            cursor: 0,
+            status: Status::Paused,
+            speed: SPEEDS.iter().position(|&speed| speed == 10).unwrap(),
+            steps: 0,
+        }
+    }
+
+    pub fn inspect(&self) -> &dyn Inspect {
+        self.execution.inspect()
+    }
+
+    pub fn program(&self) -> &[char] {
+        &self.program
+    }
+
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    pub fn output(&self) -> &SharedOutput {
+        &self.output
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// In symbols per second.
+    pub fn speed(&self) -> u32 {
+        SPEEDS[self.speed]
+    }
+
+    /// How many symbols have been interpreted.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The frames of the programs being run, outermost first. If the program that
+    /// was started is running an operation, its offset is just past the symbol
+    /// that did that.
+    pub fn frames(&self) -> Vec<Frame> {
+        self.inspect().active_frames()
+    }
+
+    pub fn scopes(&self) -> Vec<Scope> {
+        self.inspect().scopes()
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self.status, Status::Finished | Status::Failed(_))
+    }
+
+    /// Interpret a single symbol, pausing if the program was running.
+    pub fn step(&mut self) {
+        if !self.is_done() {
+            self.status = Status::Paused;
+            self.advance();
+        }
+    }
+
+    pub fn toggle_running(&mut self) {
+        self.status = match self.status {
+            Status::Paused => Status::Running,
+            Status::Running | Status::Skipping => Status::Paused,
+            ref done => done.clone(),
+        };
+    }
+
+    pub fn skip_to_breakpoint(&mut self) {
+        if !self.is_done() {
+            self.status = Status::Skipping;
+        }
+    }
+
+    /// Interpret up to `steps` symbols if the program is running, stopping at
+    /// breakpoints.
+    pub fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            if !matches!(self.status, Status::Running | Status::Skipping) {
+                return;
+            }
+
+            self.advance();
+            let frames = self.frames();
+            if frames.len() == 1 && self.breakpoints.contains(&frames[0].offset) {
+                self.status = Status::Paused;
+            }
+        }
+    }
+
+    fn advance(&mut self) {
+        match self.execution.step() {
+            Ok(true) => self.steps += 1,
+            Ok(false) => self.status = Status::Finished,
+            Err(report) => self.status = Status::Failed(report),
+        }
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    pub fn toggle_breakpoint(&mut self) {
+        if self.cursor < self.program.len() && !self.breakpoints.remove(&self.cursor) {
+            self.breakpoints.insert(self.cursor);
+        }
+    }
+
+    /// Move the cursor by `delta` symbols.
+    pub fn move_cursor(&mut self, delta: isize) {
+        let last = self.program.len().saturating_sub(1);
+        self.cursor = (self.cursor as isize + delta).clamp(0, last as isize) as usize;
+    }
+
+    /// Move the cursor to the line `delta` lines away, keeping the column if that
+    /// line is long enough.
+    pub fn move_cursor_lines(&mut self, delta: isize) {
+        let lines = self.lines();
+        let current = lines
+            .iter()
+            .rposition(|line| line.start <= self.cursor)
+            .unwrap_or(0);
+        let column = self.cursor - lines[current].start;
+        let target = &lines[(current as isize + delta).clamp(0, lines.len() as isize - 1) as usize];
+
+        self.cursor = (target.start + column).min(target.end.max(target.start + 1) - 1);
+        self.cursor = self.cursor.min(self.program.len().saturating_sub(1));
+    }
+
+    /// The offsets of the lines of the program, including their newlines.
+    pub fn lines(&self) -> Vec<Range<usize>> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for (offset, &c) in self.program.iter().enumerate() {
+            if c == '\n' {
+                lines.push(start..offset + 1);
+                start = offset + 1;
+            }
+        }
+        lines.push(start..self.program.len());
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use emmental::language::Emmental;
+
+    use super::*;
+    use crate::Runner;
+
+    fn visualize(source: &str) -> Visualizer {
+        let input = Input::new(b"xy".to_vec());
+        let output = SharedOutput::default();
+        let execution = Runner::start(
+            &Emmental::default(),
+            source.as_bytes(),
+            input.clone(),
+            output.clone(),
+        );
+        Visualizer::new(execution.unwrap(), input, output)
+    }
+
+    #[test]
+    fn run_to_breakpoint() {
+        let mut visualizer = visualize("#65.\n#66.");
+        visualizer.move_cursor_lines(1);
+        visualizer.move_cursor(3);
+        visualizer.toggle_breakpoint();
+        assert_eq!(visualizer.breakpoints().iter().collect::<Vec<_>>(), [&8]);
+
+        visualizer.skip_to_breakpoint();
+        visualizer.run(100);
+        assert_eq!(visualizer.status(), &Status::Paused);
+        assert_eq!(visualizer.frames()[0].offset, 8);
+        assert_eq!(visualizer.output().contents(), b"A");
+
+        visualizer.toggle_running();
+        visualizer.run(100);
+        assert_eq!(visualizer.status(), &Status::Finished);
+        assert_eq!(visualizer.output().contents(), b"AB");
+        assert_eq!(visualizer.steps(), 9);
+    }
+
+    #[test]
+    fn step_into_operations() {
+        let mut visualizer = visualize(";#35#57#56#46#97!#97?,");
+        while visualizer.frames().len() < 2 {
+            visualizer.step();
+        }
+        assert_eq!(visualizer.inspect().program(1), ['#', '9', '8', '.']);
+
+        for _ in 0..5 {
+            visualizer.step();
+        }
+        assert_eq!(visualizer.input().progress(), (&b"xy"[..], 1));
+        assert_eq!(visualizer.status(), &Status::Paused);
+
+        visualizer.step();
+        assert_eq!(visualizer.status(), &Status::Finished);
+    }
+
+    #[test]
+    fn errors_end_the_run() {
+        let mut visualizer = visualize("+");
+        visualizer.step();
+
+        assert!(matches!(visualizer.status(), Status::Failed(_)));
+        visualizer.toggle_running();
+        assert!(matches!(visualizer.status(), Status::Failed(_)));
+    }
+
+    #[test]
+    fn speed_is_bounded() {
+        let mut visualizer = visualize("");
+        for _ in 0..SPEEDS.len() {
+            visualizer.slower();
+        }
+        assert_eq!(visualizer.speed(), 1);
+
+        visualizer.faster();
+        assert_eq!(visualizer.speed(), 2);
+    }
+}
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use mascarpone::budget::Budget;
+
+// The first line of the data is used as input, the rest is the program itself.
+fuzz_target!(|data: &str| {
+    let (input, program) = data.split_once('\n').unwrap_or(("", data));
+
+    let _ = mascarpone::compute_with_budget(program, input, Budget::steps(100_000));
+});
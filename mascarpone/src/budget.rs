@@ -0,0 +1,59 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::{Error, Result};
+
+/// Limits how long a program may run.
+///
+/// Every symbol that gets interpreted counts as one step, including those in the
+/// programs of performed operations.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    steps: Option<u64>,
+    cancelled: Option<Arc<AtomicBool>>,
+}
+
+impl Budget {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn steps(steps: u64) -> Self {
+        Self {
+            steps: Some(steps),
+            cancelled: None,
+        }
+    }
+
+    /// Stop execution as soon as `flag` is set, e.g. from another thread.
+    pub fn with_cancellation(self, flag: Arc<AtomicBool>) -> Self {
+        Self {
+            cancelled: Some(flag),
+            ..self
+        }
+    }
+
+    /// The number of steps left, if the number of steps is limited at all.
+    pub fn remaining(&self) -> Option<u64> {
+        self.steps
+    }
+
+    pub(crate) fn spend(&mut self) -> Result<()> {
+        if let Some(flag) = &self.cancelled {
+            if flag.load(Ordering::Relaxed) {
+                return Err(Error::Cancelled);
+            }
+        }
+
+        match &mut self.steps {
+            Some(0) => Err(Error::OutOfBudget),
+            Some(steps) => {
+                *steps -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,358 @@
+//! Building interpreters from Rust, without having to assemble mappings by hand.
+//!
+//! ```
+//! use mascarpone::{builder::Builder, operation::Intrinsic};
+//!
+//! let interp = Builder::initial()
+//!     .intrinsic('s', Intrinsic::Swap)
+//!     // Output the top two symbols, using `s` as defined above.
+//!     .program('p', "s..")
+//!     .build()
+//!     .unwrap();
+//! ```
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    interpreter::{Interpreter, Variant},
+    operation::{Intrinsic, Operation},
+    Error, Result, Symbol, STRING_LEFT_DELIM, STRING_RIGHT_DELIM,
+};
+
+/// Builds an interpreter step by step. Steps that can't be applied, such as
+/// installing into a quoting interpreter, make `build` fail, as do programs with
+/// unclosed strings and quoting interpreters without a parent, including those of
+/// the programs installed.
+#[derive(Debug)]
+pub struct Builder {
+    interpreter: Interpreter,
+    error: Option<Error>,
+}
+
+impl Builder {
+    pub fn new(interpreter: Interpreter) -> Self {
+        Self {
+            interpreter,
+            error: None,
+        }
+    }
+
+    pub fn initial() -> Self {
+        Self::new(Interpreter::initial())
+    }
+
+    pub fn uniform(op: Operation) -> Self {
+        Self::new(Interpreter::uniform(op))
+    }
+
+    pub fn operation(mut self, sym: Symbol, op: Operation) -> Self {
+        if self.error.is_none() {
+            self.error = self.interpreter.install(sym, op).err();
+        }
+        self
+    }
+
+    pub fn intrinsic(self, sym: Symbol, op: Intrinsic) -> Self {
+        self.operation(sym, Operation::Intrinsic(op))
+    }
+
+    /// Install a program that is interpreted by the interpreter as it has been built
+    /// so far.
+    pub fn program(self, sym: Symbol, program: &str) -> Self {
+        let interp = self.interpreter.clone();
+        self.program_with(sym, program, interp)
+    }
+
+    pub fn program_with(self, sym: Symbol, program: &str, interp: Interpreter) -> Self {
+        self.operation(
+            sym,
+            Operation::Program(program.chars().collect(), Box::new(interp)),
+        )
+    }
+
+    /// Set the operation for symbols that haven't been installed.
+    pub fn default_operation(mut self, op: Operation) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+
+        let variant = match self.interpreter.variant() {
+            Variant::Null => Err(Error::NullInterpreter),
+            Variant::QuoteString | Variant::QuoteSymbol => Err(Error::WrongInterpreterVariant),
+            Variant::Initial => Ok(Variant::Mapping {
+                mapping: Operation::intrinsic_mapping(),
+                default: op,
+            }),
+            Variant::Mapping { mapping, .. } => Ok(Variant::Mapping {
+                mapping: mapping.clone(),
+                default: op,
+            }),
+        };
+
+        match variant {
+            Ok(variant) => {
+                let parent = self.interpreter.parent().cloned();
+                self.interpreter = Interpreter::new(variant);
+                self.interpreter.set_parent(parent);
+            }
+            Err(e) => self.error = Some(e),
+        }
+        self
+    }
+
+    pub fn parent(mut self, parent: Interpreter) -> Self {
+        self.interpreter.set_parent(Some(parent));
+        self
+    }
+
+    pub fn no_parent(mut self) -> Self {
+        self.interpreter.set_parent(None);
+        self
+    }
+
+    pub fn build(self) -> Result<Interpreter> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        check(&self.interpreter)?;
+        Ok(self.interpreter)
+    }
+}
+
+// Check `interp`'s tower, along with the interpreters of the programs installed in
+// it, and theirs in turn. Towers can be deep, so this goes through them in a loop,
+// checking each interpreter once.
+fn check(interp: &Interpreter) -> Result<()> {
+    let mut checked = HashSet::new();
+    let mut unchecked = VecDeque::from([interp]);
+
+    while let Some(interp) = unchecked.pop_front() {
+        if !checked.insert(interp) {
+            continue;
+        }
+
+        match interp.variant() {
+            Variant::Null => return Err(Error::NullInterpreter),
+            // These switch to their parent once they're done quoting.
+            Variant::QuoteString | Variant::QuoteSymbol if interp.parent().is_none() => {
+                return Err(Error::NoParent)
+            }
+            Variant::Mapping { mapping, default } => {
+                let mut programs = mapping
+                    .iter()
+                    .map(|(&sym, op)| (Some(sym), op))
+                    .collect::<Vec<_>>();
+                programs.sort_unstable_by_key(|&(sym, _)| sym);
+                programs.push((None, default));
+
+                for (sym, op) in programs {
+                    if let Operation::Program(program, interp) = op {
+                        check_delimiters(program, interp, sym)?;
+                        unchecked.push_back(interp);
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        unchecked.extend(interp.parent());
+    }
+
+    Ok(())
+}
+
+// Check that a program installed for `sym` (or as the default) closes every string
+// it opens, as far as its interpreter treats the delimiters as such. A closing
+// delimiter outside of a string does nothing, so that's fine.
+fn check_delimiters(program: &[Symbol], interp: &Interpreter, sym: Option<Symbol>) -> Result<()> {
+    let error = |offset: usize, problem: &str| {
+        let line = 1 + program[..offset].iter().filter(|&&s| s == '\n').count();
+        let message = match sym {
+            Some(sym) => format!("{} in the program for {:?}", problem, sym),
+            None => format!("{} in the default program", problem),
+        };
+        Err(Error::Syntax { line, message })
+    };
+
+    let mut opened = Vec::new();
+    let mut symbols = program.iter().enumerate();
+    while let Some((offset, &s)) = symbols.next() {
+        if !opened.is_empty() {
+            match s {
+                STRING_LEFT_DELIM => opened.push(offset),
+                STRING_RIGHT_DELIM => {
+                    opened.pop();
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        match interp.extract(s) {
+            Ok(Operation::Intrinsic(Intrinsic::QuoteString)) => opened.push(offset),
+            Ok(Operation::Intrinsic(Intrinsic::QuoteSymbol)) => {
+                symbols.next();
+            }
+            _ => (),
+        }
+    }
+
+    match opened.first() {
+        Some(&offset) => error(offset, "unclosed string"),
+        None => Ok(()),
+    }
+}
+
+impl From<Interpreter> for Builder {
+    fn from(interpreter: Interpreter) -> Self {
+        Self::new(interpreter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn build_initial_is_initial() {
+        assert_eq!(Builder::initial().build().unwrap(), Interpreter::initial());
+    }
+
+    #[test]
+    fn build_mapping() {
+        let interp = Builder::initial()
+            .intrinsic('s', Intrinsic::Swap)
+            .program('p', "s..")
+            .parent(Interpreter::uniform(Operation::Intrinsic(Intrinsic::Dup)))
+            .build()
+            .unwrap();
+
+        let mut swap = Interpreter::initial();
+        swap.install('s', Operation::Intrinsic(Intrinsic::Swap))
+            .unwrap();
+        let mut expected = swap.clone();
+        expected
+            .install('p', Operation::Program(vec!['s', '.', '.'], Box::new(swap)))
+            .unwrap();
+        expected.set_parent(Some(Interpreter::uniform(Operation::Intrinsic(
+            Intrinsic::Dup,
+        ))));
+
+        assert_eq!(interp, expected);
+    }
+
+    #[test]
+    fn build_default() {
+        let output = Operation::Intrinsic(Intrinsic::Output);
+        let interp = Builder::new(Interpreter::mapping(HashMap::new()))
+            .default_operation(output.clone())
+            .build()
+            .unwrap();
+        assert_eq!(interp, Interpreter::uniform(output.clone()));
+
+        let interp = Builder::initial()
+            .default_operation(output.clone())
+            .build()
+            .unwrap();
+        assert_eq!(interp.extract('x').unwrap(), output);
+        assert_eq!(
+            interp.extract('.').unwrap(),
+            Operation::Intrinsic(Intrinsic::Output)
+        );
+        assert_eq!(
+            interp.extract('!').unwrap(),
+            Operation::Intrinsic(Intrinsic::Perform)
+        );
+    }
+
+    #[test]
+    fn build_default_keeps_parent() {
+        let interp = Builder::initial()
+            .parent(Interpreter::initial())
+            .default_operation(Operation::Intrinsic(Intrinsic::Dup))
+            .build()
+            .unwrap();
+
+        assert_eq!(interp.parent(), Some(&Interpreter::initial()));
+    }
+
+    #[test]
+    fn build_checks_string_delimiters() {
+        let result = Builder::initial().program('p', "[a[b]").build();
+        assert!(matches!(result, Err(Error::Syntax { line: 1, .. })));
+
+        let result = Builder::initial().program('p', "'a.\n[.").build();
+        assert!(matches!(result, Err(Error::Syntax { line: 2, .. })));
+
+        // Closing delimiters outside of strings do nothing.
+        Builder::initial().program('p', "].").build().unwrap();
+
+        // Quoted delimiters and delimiters in strings don't count.
+        Builder::initial()
+            .program('p', "'].[[a]'[]]'[.")
+            .build()
+            .unwrap();
+
+        // Nor do symbols that the program's interpreter doesn't quote with.
+        let quiet = Builder::uniform(Operation::Intrinsic(Intrinsic::Discard))
+            .build()
+            .unwrap();
+        Builder::initial()
+            .program_with('p', "[", quiet)
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn build_checks_nested_programs() {
+        let mut inner = Interpreter::initial();
+        inner
+            .install('q', Operation::Program(vec!['['], Box::default()))
+            .unwrap();
+        let result = Builder::initial().program_with('p', "q", inner).build();
+        assert!(matches!(result, Err(Error::Syntax { .. })));
+
+        let result = Builder::initial()
+            .program_with('p', "a", Interpreter::quote_symbol())
+            .build();
+        assert!(matches!(result, Err(Error::NoParent)));
+    }
+
+    #[test]
+    fn build_checks_parents() {
+        assert!(matches!(
+            Builder::new(Interpreter::quote_string()).build(),
+            Err(Error::NoParent)
+        ));
+        assert!(matches!(
+            Builder::initial()
+                .parent(Interpreter::quote_symbol())
+                .build(),
+            Err(Error::NoParent)
+        ));
+
+        Builder::new(Interpreter::quote_string())
+            .parent(Interpreter::initial())
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn build_fails_on_quoting_interpreter() {
+        let result = Builder::new(Interpreter::quote_symbol())
+            .intrinsic('a', Intrinsic::Output)
+            .no_parent()
+            .build();
+
+        assert!(matches!(result, Err(Error::WrongInterpreterVariant)));
+        assert!(matches!(
+            Builder::from(Interpreter::quote_string())
+                .default_operation(Operation::Intrinsic(Intrinsic::NoOp))
+                .build(),
+            Err(Error::WrongInterpreterVariant)
+        ));
+    }
+}
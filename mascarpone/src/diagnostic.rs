@@ -0,0 +1,93 @@
+use std::fmt;
+
+use crate::{operation::Intrinsic, Error, Symbol};
+
+/// Extra information about where and how an error occurred.
+#[derive(Debug, Clone)]
+pub struct Context {
+    /// The intrinsic operation that failed, if the error came from one.
+    pub intrinsic: Option<Intrinsic>,
+    /// The position in the program being executed, if the error happened while
+    /// executing one.
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Location {
+    /// The offset (in symbols) of `symbol` within the program.
+    pub offset: usize,
+    pub symbol: Symbol,
+    /// The number of interpreters in the tower that was interpreting `symbol`,
+    /// i.e. the interpreter itself plus all of its ancestors.
+    pub depth: usize,
+    /// The number of programs that were being executed, see `State::call_depth`.
+    pub call_depth: usize,
+    /// The topmost elements of the stack at the time the error was raised, topmost
+    /// first. Note that anything the failing operation already popped is no longer
+    /// part of this.
+    pub stack: Vec<String>,
+    /// How many elements were on the stack in total.
+    pub stack_size: usize,
+}
+
+impl Location {
+    /// The maximum number of stack elements included in a location.
+    pub const STACK_SNAPSHOT_SIZE: usize = 5;
+}
+
+pub struct Report<'e> {
+    error: &'e Error,
+}
+
+impl<'e> Report<'e> {
+    pub(crate) fn new(error: &'e Error) -> Self {
+        Self { error }
+    }
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "error: {}", self.error.root())?;
+
+        let context = match self.error.context() {
+            Some(context) => context,
+            None => return Ok(()),
+        };
+
+        if let Some(location) = &context.location {
+            writeln!(
+                f,
+                "  at offset {}, symbol {:?}",
+                location.offset, location.symbol
+            )?;
+        }
+
+        if let Some(intrinsic) = context.intrinsic {
+            match intrinsic.to_symbol() {
+                Some(sym) => writeln!(f, "  in intrinsic {:?} ({:?})", intrinsic, sym)?,
+                None => writeln!(f, "  in intrinsic {:?}", intrinsic)?,
+            }
+        }
+
+        if let Some(location) = &context.location {
+            writeln!(f, "  interpreter depth: {}", location.depth)?;
+            writeln!(f, "  call depth: {}", location.call_depth)?;
+
+            if location.stack.is_empty() {
+                writeln!(f, "  stack is empty")?;
+            } else {
+                writeln!(f, "  stack (topmost first):")?;
+                for element in &location.stack {
+                    writeln!(f, "    {}", element)?;
+                }
+
+                let remaining = location.stack_size - location.stack.len();
+                if remaining > 0 {
+                    writeln!(f, "    ... and {} more", remaining)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+//! Rendering interpreters as Graphviz graphs, since the towers that reflective
+//! programs build are hard to make sense of otherwise.
+//!
+//! Every interpreter becomes a node, listing only what it does differently from
+//! the initial interpreter. Edges lead to its parent, and to the interpreters of
+//! any programs it performs. Equal interpreters share a node.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+};
+
+use crate::{
+    interpreter::{Interpreter, Variant},
+    operation::{Intrinsic, Operation},
+    Symbol,
+};
+
+/// Write an interpreter and everything it refers to as a DOT graph.
+pub fn write_dot(interp: &Interpreter) -> String {
+    let mut graph = Graph {
+        out: String::new(),
+        ids: HashMap::new(),
+        queue: VecDeque::new(),
+    };
+
+    graph.out.push_str("digraph interpreters {\n");
+    graph
+        .out
+        .push_str("    node [shape=box, fontname=monospace];\n");
+    graph.id(interp);
+    // Towers can be deep, so go through them in a loop rather than recursing.
+    while let Some((id, interp)) = graph.queue.pop_front() {
+        graph.interpreter(id, interp);
+    }
+    graph.out.push_str("}\n");
+    graph.out
+}
+
+struct Graph<'i> {
+    out: String,
+    ids: HashMap<&'i Interpreter, usize>,
+    /// Interpreters that have an id, but no node yet.
+    queue: VecDeque<(usize, &'i Interpreter)>,
+}
+
+impl<'i> Graph<'i> {
+    /// The id of the node for `interp`, queueing a new node if there isn't one.
+    fn id(&mut self, interp: &'i Interpreter) -> usize {
+        if let Some(&id) = self.ids.get(interp) {
+            return id;
+        }
+
+        let id = self.ids.len();
+        self.ids.insert(interp, id);
+        self.queue.push_back((id, interp));
+        id
+    }
+
+    /// Add the node for `interp` and its edges.
+    fn interpreter(&mut self, id: usize, interp: &'i Interpreter) {
+        let mut lines = vec![interp.variant().name().to_string()];
+        let mut edges = Vec::new();
+
+        if let Variant::Mapping { mapping, default } = interp.variant() {
+            let mut symbols = mapping
+                .keys()
+                .copied()
+                .chain(Intrinsic::SYMBOLS.iter().map(|&(_, sym)| sym))
+                .collect::<Vec<_>>();
+            symbols.sort_unstable();
+            symbols.dedup();
+
+            for sym in symbols {
+                match mapping.get(&sym) {
+                    Some(op) if is_initial(sym, op) => (),
+                    Some(op) => {
+                        lines.push(format!("{:?}: {}", sym, op));
+                        if let Operation::Program(_, interp) = op {
+                            edges.push((format!("{:?}", sym), &**interp));
+                        }
+                    }
+                    None if is_initial(sym, default) => (),
+                    None => lines.push(format!("{:?}: default", sym)),
+                }
+            }
+
+            if !matches!(default, Operation::Intrinsic(Intrinsic::NoOp)) {
+                lines.push(format!("default: {}", default));
+                if let Operation::Program(_, interp) = default {
+                    edges.push(("default".to_string(), &**interp));
+                }
+            }
+        }
+
+        let label = lines
+            .iter()
+            .map(|line| escape(line) + "\\l")
+            .collect::<String>();
+        writeln!(self.out, "    n{} [label=\"{}\"];", id, label).unwrap();
+
+        for (label, interp) in edges {
+            let child = self.id(interp);
+            writeln!(
+                self.out,
+                "    n{} -> n{} [label=\"{}\"];",
+                id,
+                child,
+                escape(&label)
+            )
+            .unwrap();
+        }
+
+        if let Some(parent) = interp.parent() {
+            let parent = self.id(parent);
+            writeln!(
+                self.out,
+                "    n{} -> n{} [label=\"parent\", style=dashed];",
+                id, parent
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Whether the initial interpreter does the same thing for `sym`.
+fn is_initial(sym: Symbol, op: &Operation) -> bool {
+    let initial = Intrinsic::from_symbol(sym).unwrap_or(Intrinsic::NoOp);
+    matches!(op, Operation::Intrinsic(op) if *op == initial)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dot_initial_interpreter() {
+        assert_eq!(
+            write_dot(&Interpreter::initial()),
+            "digraph interpreters {\n    \
+                node [shape=box, fontname=monospace];\n    \
+                n0 [label=\"initial\\l\"];\n\
+            }\n"
+        );
+    }
+
+    #[test]
+    fn dot_only_shows_differences_from_initial() {
+        let mut interp = Interpreter::initial();
+        interp
+            .install('.', Operation::Intrinsic(Intrinsic::Output))
+            .unwrap();
+        interp
+            .install('x', Operation::Intrinsic(Intrinsic::Swap))
+            .unwrap();
+        interp
+            .install('"', Operation::Intrinsic(Intrinsic::NoOp))
+            .unwrap();
+
+        let dot = write_dot(&interp);
+        assert!(dot.contains("n0 [label=\"mapping\\l'x': intrinsic Swap\\l\"];"));
+        assert!(!dot.contains("->"));
+    }
+
+    #[test]
+    fn dot_follows_programs() {
+        let mut inner = Interpreter::quote_string();
+        inner.set_parent(Some(Interpreter::initial()));
+        let mut interp = Interpreter::uniform(Operation::Program(vec!['"', '.'], Box::new(inner)));
+        interp
+            .install('a', Operation::Intrinsic(Intrinsic::Output))
+            .unwrap();
+
+        let dot = write_dot(&interp);
+        assert!(dot.contains("'a': intrinsic Output\\l"));
+        assert!(dot.contains("'!': default\\l"));
+        assert!(dot.contains("default: program \\\"\\\\\\\".\\\"\\l"));
+        assert!(dot.contains("n0 -> n1 [label=\"default\"];"));
+        assert!(dot.contains("n1 [label=\"string quoting\\l\"];"));
+        assert!(dot.contains("n1 -> n2 [label=\"parent\", style=dashed];"));
+    }
+
+    #[test]
+    fn dot_shares_equal_interpreters() {
+        let program = Operation::Program(vec!['.'], Box::new(Interpreter::quote_symbol()));
+        let mut interp = Interpreter::uniform(program.clone());
+        interp.install('a', program).unwrap();
+        interp.set_parent(Some(Interpreter::quote_symbol()));
+
+        let dot = write_dot(&interp);
+        assert!(dot.contains("n0 -> n1 [label=\"'a'\"];"));
+        assert!(dot.contains("n0 -> n1 [label=\"default\"];"));
+        assert!(dot.contains("n0 -> n1 [label=\"parent\", style=dashed];"));
+        assert!(!dot.contains("n2"));
+    }
+
+    #[test]
+    fn dot_deep_tower() {
+        let tower = (0..1_000_000).fold(Interpreter::initial(), |parent, _| {
+            let mut interp = Interpreter::quote_symbol();
+            interp.set_parent(Some(parent));
+            interp
+        });
+
+        let dot = write_dot(&tower);
+        assert!(dot.contains("n999999 -> n1000000 [label=\"parent\", style=dashed];"));
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    fmt,
+    io::{self, Read, Write},
+    str::FromStr,
+};
+
+use esolang_core::io::SymbolIO;
+
+use crate::{Error, Result, Symbol};
+
+/// How symbols are represented as bytes when reading input and writing output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// Every byte is one symbol, namely the code point with the same value. This
+    /// also means only the first 256 code points can be written.
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Utf8 => "UTF-8",
+            Self::Latin1 => "Latin-1",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+        })
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" | "bytes" => Ok(Self::Latin1),
+            "utf-16le" | "utf16le" => Ok(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Self::Utf16Be),
+            _ => Err(format!("unknown encoding: {}", s)),
+        }
+    }
+}
+
+impl Encoding {
+    /// Write `sym` to `writer` in this encoding.
+    pub fn encode<W: Write>(self, sym: Symbol, writer: &mut W) -> Result<()> {
+        let mut buf = [0; 4];
+
+        let bytes: &[u8] = match self {
+            Self::Utf8 => sym.encode_utf8(&mut buf).as_bytes(),
+            Self::Latin1 => {
+                buf[0] =
+                    u8::try_from(u32::from(sym)).map_err(|_| Error::UnrepresentableSymbol {
+                        symbol: sym,
+                        encoding: self,
+                    })?;
+                &buf[..1]
+            }
+            Self::Utf16Le | Self::Utf16Be => {
+                let mut units = [0; 2];
+                let units = sym.encode_utf16(&mut units);
+                for (i, unit) in units.iter().enumerate() {
+                    let unit_bytes = if self == Self::Utf16Le {
+                        unit.to_le_bytes()
+                    } else {
+                        unit.to_be_bytes()
+                    };
+                    buf[2 * i..2 * i + 2].copy_from_slice(&unit_bytes);
+                }
+                &buf[..2 * units.len()]
+            }
+        };
+
+        writer.write_all(bytes)?;
+        Ok(())
+    }
+}
+
+/// Reads symbols from a byte stream.
+///
+/// Any bytes that are read but not yet decoded are kept around until the next
+/// symbol is read, so a decoder should be kept for as long as the stream is being
+/// read from.
+#[derive(Debug, Clone, Default)]
+pub struct Decoder {
+    encoding: Encoding,
+    buffer: VecDeque<u8>,
+}
+
+impl Decoder {
+    const CHUNK_SIZE: usize = 64;
+
+    pub fn new(encoding: Encoding) -> Self {
+        Self {
+            encoding,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Read the next symbol from `reader`, or `None` if the end of the input has been
+    /// reached.
+    pub fn read_symbol<R: Read>(&mut self, reader: &mut R) -> Result<Option<Symbol>> {
+        if !self.fill(reader, 1)? {
+            return Ok(None);
+        }
+
+        match self.encoding {
+            Encoding::Latin1 => Ok(self.buffer.pop_front().map(char::from)),
+            Encoding::Utf8 => {
+                let len = match self.buffer[0] {
+                    0x00..=0x7f => 1,
+                    0xc2..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf4 => 4,
+                    _ => return Err(self.invalid(1)),
+                };
+
+                // If the rest of the symbol isn't there yet, this fails and keeps
+                // the bytes for the next try.
+                let complete = self.fill(reader, len)?;
+                let bytes = self.buffer.iter().take(len).copied().collect::<Vec<_>>();
+                match std::str::from_utf8(&bytes) {
+                    Ok(s) => {
+                        self.buffer.drain(..len);
+                        Ok(s.chars().next())
+                    }
+                    // Only the invalid bytes are consumed, as the ones after them may
+                    // start a valid sequence.
+                    Err(e) => Err(self.invalid(match e.error_len() {
+                        Some(len) => len,
+                        // The input ended in the middle of the symbol.
+                        None if !complete => bytes.len(),
+                        None => {
+                            unreachable!("a symbol that's all there is valid or has invalid bytes")
+                        }
+                    })),
+                }
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                // Make sure the unit after a high surrogate has been read before
+                // consuming either, so neither is lost if it isn't there yet.
+                if matches!(self.peek_unit(reader)?, Some(0xd800..=0xdbff)) {
+                    self.fill(reader, 4)?;
+                }
+
+                let high = self.read_unit(reader)?;
+                let units = if (0xd800..0xdc00).contains(&high) {
+                    // A unit that isn't a low surrogate is left for the next symbol.
+                    match self.peek_unit(reader)? {
+                        Some(low) if (0xdc00..0xe000).contains(&low) => {
+                            self.buffer.drain(..2);
+                            vec![high, low]
+                        }
+                        _ => vec![high],
+                    }
+                } else {
+                    vec![high]
+                };
+
+                match char::decode_utf16(units.iter().copied()).next() {
+                    Some(Ok(sym)) => Ok(Some(sym)),
+                    _ => Err(Error::InvalidInput {
+                        encoding: self.encoding,
+                        bytes: self.unit_bytes(&units),
+                    }),
+                }
+            }
+        }
+    }
+
+    // Make sure there are at least `len` bytes in the buffer, returning false if the
+    // input ran out before that.
+    fn fill<R: Read>(&mut self, reader: &mut R, len: usize) -> io::Result<bool> {
+        let mut chunk = [0; Self::CHUNK_SIZE];
+
+        while self.buffer.len() < len {
+            match reader.read(&mut chunk) {
+                Ok(0) => return Ok(false),
+                Ok(n) => self.buffer.extend(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn read_unit<R: Read>(&mut self, reader: &mut R) -> Result<u16> {
+        match self.peek_unit(reader)? {
+            Some(unit) => {
+                self.buffer.drain(..2);
+                Ok(unit)
+            }
+            None => Err(self.invalid(self.buffer.len())),
+        }
+    }
+
+    // The next unit, without consuming it, or `None` if the input ran out first.
+    fn peek_unit<R: Read>(&mut self, reader: &mut R) -> io::Result<Option<u16>> {
+        if !self.fill(reader, 2)? {
+            return Ok(None);
+        }
+
+        let bytes = [self.buffer[0], self.buffer[1]];
+        Ok(Some(if self.encoding == Encoding::Utf16Le {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        }))
+    }
+
+    fn unit_bytes(&self, units: &[u16]) -> Vec<u8> {
+        units
+            .iter()
+            .flat_map(|unit| {
+                if self.encoding == Encoding::Utf16Le {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                }
+            })
+            .collect()
+    }
+
+    // Consume `len` bytes, and report them as invalid.
+    fn invalid(&mut self, len: usize) -> Error {
+        Error::InvalidInput {
+            encoding: self.encoding,
+            bytes: self.buffer.drain(..len).collect(),
+        }
+    }
+}
+
+/// Symbol IO over a byte stream, such as an `InputOutputPair`, which reads and
+/// writes symbols in some encoding.
+#[derive(Debug)]
+pub struct Encoded<IO> {
+    io: IO,
+    decoder: Decoder,
+}
+
+impl<IO> Encoded<IO> {
+    pub fn new(io: IO, encoding: Encoding) -> Self {
+        Self {
+            io,
+            decoder: Decoder::new(encoding),
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.decoder.encoding()
+    }
+
+    pub fn get_ref(&self) -> &IO {
+        &self.io
+    }
+
+    /// Note that bytes that have been read, but not decoded yet, are lost.
+    pub fn into_inner(self) -> IO {
+        self.io
+    }
+}
+
+impl<IO: Read + Write> SymbolIO<Symbol> for Encoded<IO> {
+    type Error = Error;
+
+    fn read_symbol(&mut self) -> Result<Option<Symbol>> {
+        self.decoder.read_symbol(&mut self.io)
+    }
+
+    fn write_symbol(&mut self, sym: Symbol) -> Result<()> {
+        self.decoder.encoding().encode(sym, &mut self.io)
+    }
+
+    /// Input that would block hasn't arrived yet. Whatever part of a symbol did
+    /// arrive is kept for the next read.
+    fn is_pending(error: &Error) -> bool {
+        matches!(error, Error::IOError(e) if e.kind() == io::ErrorKind::WouldBlock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(encoding: Encoding, bytes: &[u8]) -> Result<String> {
+        let mut decoder = Decoder::new(encoding);
+        let mut reader = bytes;
+        let mut string = String::new();
+
+        while let Some(sym) = decoder.read_symbol(&mut reader)? {
+            string.push(sym);
+        }
+
+        Ok(string)
+    }
+
+    fn encode_all(encoding: Encoding, string: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for sym in string.chars() {
+            encoding.encode(sym, &mut bytes)?;
+        }
+
+        Ok(bytes)
+    }
+
+    #[test]
+    fn decode_utf8() {
+        assert_eq!(decode_all(Encoding::Utf8, "a€𝄞".as_bytes()).unwrap(), "a€𝄞");
+    }
+
+    #[test]
+    fn decode_utf8_invalid() {
+        assert!(matches!(
+            decode_all(Encoding::Utf8, b"a\xe2\x82"),
+            Err(Error::InvalidInput { bytes, .. }) if bytes == b"\xe2\x82"
+        ));
+        assert!(decode_all(Encoding::Utf8, b"\xff").is_err());
+    }
+
+    /// Input that would block once it runs out, rather than end.
+    struct Blocking<'a>(&'a [u8]);
+
+    impl Read for Blocking<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.read(buf)? {
+                0 if !buf.is_empty() => Err(io::ErrorKind::WouldBlock.into()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    #[test]
+    fn decode_partial() {
+        for (encoding, sym) in [(Encoding::Utf8, '€'), (Encoding::Utf16Le, '𝄞')] {
+            let mut bytes = Vec::new();
+            encoding.encode(sym, &mut bytes).unwrap();
+            let mut decoder = Decoder::new(encoding);
+
+            for len in 1..bytes.len() {
+                let result = decoder.read_symbol(&mut Blocking(&bytes[len - 1..len]));
+                assert!(matches!(
+                    result,
+                    Err(Error::IOError(e)) if e.kind() == io::ErrorKind::WouldBlock
+                ));
+            }
+            let last = &bytes[bytes.len() - 1..];
+            assert_eq!(decoder.read_symbol(&mut Blocking(last)).unwrap(), Some(sym));
+        }
+    }
+
+    #[test]
+    fn decode_utf8_after_invalid() {
+        let mut decoder = Decoder::new(Encoding::Utf8);
+        let mut reader = &b"\xe2ab"[..];
+
+        assert!(matches!(
+            decoder.read_symbol(&mut reader),
+            Err(Error::InvalidInput { bytes, .. }) if bytes == b"\xe2"
+        ));
+        assert_eq!(decoder.read_symbol(&mut reader).unwrap(), Some('a'));
+        assert_eq!(decoder.read_symbol(&mut reader).unwrap(), Some('b'));
+    }
+
+    #[test]
+    fn decode_latin1() {
+        assert_eq!(decode_all(Encoding::Latin1, b"a\xe9\xff").unwrap(), "aéÿ");
+    }
+
+    #[test]
+    fn decode_utf16() {
+        let le = encode_all(Encoding::Utf16Le, "a€𝄞").unwrap();
+        let be = encode_all(Encoding::Utf16Be, "a€𝄞").unwrap();
+
+        assert_eq!(le, b"a\x00\xac\x20\x34\xd8\x1e\xdd");
+        assert_eq!(decode_all(Encoding::Utf16Le, &le).unwrap(), "a€𝄞");
+        assert_eq!(decode_all(Encoding::Utf16Be, &be).unwrap(), "a€𝄞");
+    }
+
+    #[test]
+    fn decode_utf16_unpaired_surrogate() {
+        assert!(decode_all(Encoding::Utf16Le, b"\x34\xd8a\x00").is_err());
+        assert!(decode_all(Encoding::Utf16Le, b"\x1e\xdd").is_err());
+        assert!(decode_all(Encoding::Utf16Le, b"a").is_err());
+
+        let mut decoder = Decoder::new(Encoding::Utf16Le);
+        let mut reader = &b"\x34\xd8a\x00"[..];
+        assert!(matches!(
+            decoder.read_symbol(&mut reader),
+            Err(Error::InvalidInput { bytes, .. }) if bytes == b"\x34\xd8"
+        ));
+        assert_eq!(decoder.read_symbol(&mut reader).unwrap(), Some('a'));
+    }
+
+    #[test]
+    fn encode_latin1_unrepresentable() {
+        assert!(matches!(
+            encode_all(Encoding::Latin1, "€"),
+            Err(Error::UnrepresentableSymbol { symbol: '€', .. })
+        ));
+    }
+}
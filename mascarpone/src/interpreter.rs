@@ -1,17 +1,27 @@
 use std::{
     collections::HashMap,
-    io::{Read, Write},
+    fmt,
+    hash::{Hash, Hasher},
+    iter,
+    sync::OnceLock,
 };
 
+use esolang_core::io::SymbolIO;
+use siphasher::sip::SipHasher13;
+
 use crate::{
     operation::{Intrinsic, Operation},
+    profile::NullInterpreter,
     state::Element,
     state::State,
     Error, Result, Symbol,
 };
 
 #[derive(Debug, Clone)]
-enum Variant {
+pub enum Variant {
+    /// Only used for the current interpreter, when the profile allows deifying the
+    /// null interpreter. See the note on `Interpreter`.
+    Null,
     Initial,
     QuoteString,
     QuoteSymbol,
@@ -21,22 +31,169 @@ enum Variant {
     },
 }
 
+/// How a mapping, or the initial interpreter, interprets symbols. Interpreters
+/// that do the same for every symbol compare and hash the same as these.
+enum EffectiveMapping<'a> {
+    Initial,
+    /// The symbols the mapping doesn't interpret with its default operation, in
+    /// order, and the default.
+    Mapping {
+        entries: Vec<(Symbol, &'a Operation)>,
+        default: &'a Operation,
+    },
+}
+
+impl EffectiveMapping<'_> {
+    fn is_initial(&self) -> bool {
+        match self {
+            Self::Initial => true,
+            Self::Mapping { entries, default } => {
+                **default == Operation::Intrinsic(Intrinsic::NoOp)
+                    && entries.len() == Intrinsic::SORTED_SYMBOLS.len()
+                    && entries.iter().zip(&Intrinsic::SORTED_SYMBOLS).all(
+                        |(&(sym, op), &(intrinsic, intrinsic_sym))| {
+                            sym == intrinsic_sym && *op == Operation::Intrinsic(intrinsic)
+                        },
+                    )
+            }
+        }
+    }
+}
+
+impl PartialEq for EffectiveMapping<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Initial, Self::Initial) => true,
+            (
+                Self::Mapping { entries, default },
+                Self::Mapping {
+                    entries: other_entries,
+                    default: other_default,
+                },
+            ) => entries == other_entries && default == other_default,
+            (Self::Initial, mapping) | (mapping, Self::Initial) => mapping.is_initial(),
+        }
+    }
+}
+
+// Hashes the initial interpreter as the mapping it's equal to, without building it.
+impl Hash for EffectiveMapping<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Self::Initial => {
+                Intrinsic::SORTED_SYMBOLS.len().hash(state);
+                for &(op, sym) in &Intrinsic::SORTED_SYMBOLS {
+                    sym.hash(state);
+                    Operation::Intrinsic(op).hash(state);
+                }
+                Operation::Intrinsic(Intrinsic::NoOp).hash(state);
+            }
+            Self::Mapping { entries, default } => {
+                entries.len().hash(state);
+                for (sym, op) in entries {
+                    sym.hash(state);
+                    op.hash(state);
+                }
+                default.hash(state);
+            }
+        }
+    }
+}
+
+impl Variant {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Initial => "initial",
+            Self::QuoteString => "string quoting",
+            Self::QuoteSymbol => "symbol quoting",
+            Self::Mapping { .. } => "mapping",
+        }
+    }
+
+    fn effective_mapping(&self) -> Option<EffectiveMapping<'_>> {
+        match self {
+            Self::Initial => Some(EffectiveMapping::Initial),
+            Self::Mapping { mapping, default } => {
+                let mut entries = mapping
+                    .iter()
+                    .filter(|&(_, op)| op != default)
+                    .map(|(&sym, op)| (sym, op))
+                    .collect::<Vec<_>>();
+                entries.sort_unstable_by_key(|&(sym, _)| sym);
+
+                Some(EffectiveMapping::Mapping { entries, default })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Variant {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Null, Self::Null)
+            | (Self::QuoteString, Self::QuoteString)
+            | (Self::QuoteSymbol, Self::QuoteSymbol) => true,
+            // Interpreters are usually compared to clones of themselves.
+            (
+                Self::Mapping { mapping, default },
+                Self::Mapping {
+                    mapping: other_mapping,
+                    default: other_default,
+                },
+            ) if mapping == other_mapping && default == other_default => true,
+            _ => match (self.effective_mapping(), other.effective_mapping()) {
+                (Some(lhs), Some(rhs)) => lhs == rhs,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Eq for Variant {}
+
+impl Hash for Variant {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Self::Null => 0u8.hash(state),
+            Self::QuoteString => 1u8.hash(state),
+            Self::QuoteSymbol => 2u8.hash(state),
+            Self::Initial | Self::Mapping { .. } => {
+                3u8.hash(state);
+                self.effective_mapping().hash(state);
+            }
+        }
+    }
+}
+
 // Note that there's no explicit "null interpreter"; instead, the parent interpreter is
 // optional, as are interpreter values on the stack. This means we diverge slightly from
 // what the spec explicitly describes, in that e.g. trying to deify a null interpreter is
 // already an error, rather than trying to execute an operation with it. Practically,
-// there's not a real difference.
-#[derive(Debug, Clone)]
+// there's not a real difference. Where the profile says otherwise, the state's current
+// interpreter can be the null interpreter after all.
+//
+// Interpreters are equal when they and their parents interpret every symbol the same
+// way, so e.g. the initial interpreter equals a mapping of every symbol to its
+// intrinsic.
+//
+// Towers can get as deep as a program cares to make them, so everything that goes
+// through one walks it in a loop rather than recursing into the parents.
 pub struct Interpreter {
     parent: Option<Box<Interpreter>>, // May need to use Rc instead.
     variant: Variant,
+    /// The interpreter's hash, as working that out means going through its whole
+    /// mapping and tower. Cleared whenever the interpreter changes.
+    hash: OnceLock<u64>,
 }
 
 impl Interpreter {
-    fn new(variant: Variant) -> Self {
+    pub(crate) fn new(variant: Variant) -> Self {
         Self {
             parent: None,
             variant,
+            hash: OnceLock::new(),
         }
     }
 
@@ -44,6 +201,14 @@ impl Interpreter {
         Self::new(Variant::Initial)
     }
 
+    pub(crate) fn null() -> Self {
+        Self::new(Variant::Null)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.variant, Variant::Null)
+    }
+
     pub fn quote_string() -> Self {
         Self::new(Variant::QuoteString)
     }
@@ -74,14 +239,59 @@ impl Interpreter {
 
     pub fn set_parent(&mut self, parent: Option<Interpreter>) {
         self.parent = parent.map(Box::new);
+        self.hash.take();
     }
 
     pub fn variant(&self) -> &Variant {
         &self.variant
     }
 
+    /// This interpreter and its parents, starting with this one.
+    pub fn tower(&self) -> impl Iterator<Item = &Self> {
+        iter::successors(Some(self), |i| i.parent())
+    }
+
+    /// The number of interpreters in this interpreter's tower, counting itself.
+    pub fn depth(&self) -> usize {
+        self.tower().count()
+    }
+
+    /// The number of symbols this interpreter holds on to, counting one for each
+    /// interpreter in its tower, and the sizes of the operations it maps symbols to.
+    pub fn size(&self) -> usize {
+        self.tower()
+            .map(|interp| match &interp.variant {
+                Variant::Mapping { mapping, default } => {
+                    1 + default.size() + mapping.values().map(Operation::size).sum::<usize>()
+                }
+                _ => 1,
+            })
+            .sum()
+    }
+
+    /// A hash of the interpreter that stays the same between runs and Rust releases
+    /// (on platforms with the same pointer width and endianness), so it can be used
+    /// as a key in e.g. a cache on disk. Equal interpreters have equal hashes.
+    pub fn stable_hash(&self) -> u64 {
+        // Hash the tower from the top down, so each interpreter's hash can include
+        // its parent's.
+        let unhashed = self
+            .tower()
+            .take_while(|i| i.hash.get().is_none())
+            .collect::<Vec<_>>();
+        for interp in unhashed.into_iter().rev() {
+            let mut hasher = SipHasher13::new();
+            interp.parent().and_then(|p| p.hash.get()).hash(&mut hasher);
+            interp.variant.hash(&mut hasher);
+            let _ = interp.hash.set(hasher.finish());
+        }
+
+        *self.hash.get().expect("the whole tower has been hashed")
+    }
+
     pub fn extract(&self, sym: Symbol) -> Result<Operation> {
         match self.variant {
+            Variant::Null => Err(Error::NullInterpreter),
             Variant::QuoteString | Variant::QuoteSymbol => Err(Error::WrongInterpreterVariant),
             Variant::Initial => Ok(Operation::Intrinsic(
                 Intrinsic::from_symbol(sym).unwrap_or(Intrinsic::NoOp),
@@ -94,7 +304,9 @@ impl Interpreter {
     }
 
     pub fn install(&mut self, sym: Symbol, op: Operation) -> Result<()> {
+        self.hash.take();
         match self.variant {
+            Variant::Null => Err(Error::NullInterpreter),
             Variant::QuoteString | Variant::QuoteSymbol => Err(Error::WrongInterpreterVariant),
             Variant::Initial => {
                 let mut mapping = Operation::intrinsic_mapping();
@@ -114,16 +326,21 @@ impl Interpreter {
         }
     }
 
-    pub fn interpret<IO: Read + Write>(&self, sym: Symbol, state: &mut State<IO>) -> Result<()> {
+    pub fn interpret<IO>(&self, sym: Symbol, state: &mut State<IO>) -> Result<()>
+    where
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
+    {
         match self.variant {
+            Variant::Null => match state.profile().null_interpreter {
+                NullInterpreter::Ignore => Ok(()),
+                _ => Err(Error::NullInterpreter),
+            },
             Variant::QuoteString => {
                 state.push_element(Element::Symbol(sym));
 
                 match sym {
-                    crate::STRING_RIGHT_DELIM => {
-                        // TODO: put this in `state.switch_to_parent` or something
-                        state.interpreter = self.parent().expect("parent should be defined").clone()
-                    }
+                    crate::STRING_RIGHT_DELIM => state.switch_to_parent()?,
                     crate::STRING_LEFT_DELIM => state.start_quote_string(),
                     _ => (),
                 }
@@ -132,8 +349,7 @@ impl Interpreter {
             }
             Variant::QuoteSymbol => {
                 state.push_element(Element::Symbol(sym));
-                state.interpreter = self.parent().expect("parent should be defined").clone();
-                Ok(())
+                state.switch_to_parent()
             }
             Variant::Initial => Intrinsic::from_symbol(sym)
                 .unwrap_or(Intrinsic::NoOp)
@@ -141,13 +357,188 @@ impl Interpreter {
             Variant::Mapping {
                 ref mapping,
                 ref default,
-            } => mapping.get(&sym).unwrap_or(&default).execute(state),
+            } => mapping.get(&sym).unwrap_or(default).execute(state),
+        }
+    }
+}
+
+impl Clone for Interpreter {
+    fn clone(&self) -> Self {
+        self.tower()
+            .map(|interp| Self {
+                parent: None,
+                variant: interp.variant.clone(),
+                hash: interp.hash.clone(),
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .reduce(|parent, mut interp| {
+                interp.parent = Some(Box::new(parent));
+                interp
+            })
+            .expect("a tower has at least one interpreter")
+    }
+}
+
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        let mut parent = self.parent.take();
+        while let Some(mut interp) = parent {
+            parent = interp.parent.take();
         }
     }
 }
 
+impl fmt::Debug for Interpreter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The tower, starting with this interpreter.
+        f.write_str("Interpreter ")?;
+        f.debug_list()
+            .entries(self.tower().map(|i| &i.variant))
+            .finish()
+    }
+}
+
+impl PartialEq for Interpreter {
+    fn eq(&self, other: &Self) -> bool {
+        if let (Some(lhs), Some(rhs)) = (self.hash.get(), other.hash.get()) {
+            if lhs != rhs {
+                return false;
+            }
+        }
+
+        self.tower()
+            .map(|i| &i.variant)
+            .eq(other.tower().map(|i| &i.variant))
+    }
+}
+
+impl Eq for Interpreter {}
+
+impl Hash for Interpreter {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.stable_hash());
+    }
+}
+
 impl Default for Interpreter {
     fn default() -> Self {
         Self::initial()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::native::Native;
+
+    #[test]
+    fn initial_equals_its_mapping() {
+        let mapping = Interpreter::mapping(Operation::intrinsic_mapping());
+        assert_eq!(Interpreter::initial(), mapping);
+        assert_eq!(Interpreter::initial().stable_hash(), mapping.stable_hash());
+
+        let mut installed = Interpreter::initial();
+        installed
+            .install('!', Operation::Intrinsic(Intrinsic::Perform))
+            .unwrap();
+        assert_eq!(Interpreter::initial(), installed);
+        assert_eq!(
+            Interpreter::initial().stable_hash(),
+            installed.stable_hash()
+        );
+    }
+
+    #[test]
+    fn stable_hash_is_stable() {
+        // Changing this invalidates any hashes that have been stored.
+        assert_eq!(Interpreter::initial().stable_hash(), 0x4184_d285_920a_b4aa);
+    }
+
+    #[test]
+    fn deep_towers_compare_and_hash() {
+        let tower = || {
+            (0..1_000_000).fold(Interpreter::initial(), |parent, _| {
+                let mut interp = Interpreter::quote_symbol();
+                interp.set_parent(Some(parent));
+                interp
+            })
+        };
+        let (lhs, rhs) = (tower(), tower());
+
+        assert_eq!(lhs, rhs);
+        assert_eq!(lhs.stable_hash(), rhs.stable_hash());
+        assert_ne!(lhs, *lhs.parent().unwrap());
+    }
+
+    #[test]
+    fn sorted_symbols_are_sorted() {
+        let mut symbols = Intrinsic::SYMBOLS;
+        symbols.sort_unstable_by_key(|&(_, sym)| sym);
+        assert_eq!(symbols, Intrinsic::SORTED_SYMBOLS);
+    }
+
+    #[test]
+    fn explicit_default_equals_missing_entry() {
+        let op = Operation::Intrinsic(Intrinsic::Output);
+        let mut interp = Interpreter::uniform(op.clone());
+        interp.install('a', op).unwrap();
+
+        assert_eq!(
+            interp,
+            Interpreter::uniform(Operation::Intrinsic(Intrinsic::Output))
+        );
+    }
+
+    #[test]
+    fn different_interpreters_are_not_equal() {
+        let mut installed = Interpreter::initial();
+        installed
+            .install('!', Operation::Intrinsic(Intrinsic::Dup))
+            .unwrap();
+        assert_ne!(Interpreter::initial(), installed);
+
+        let mut with_parent = Interpreter::initial();
+        with_parent.set_parent(Some(Interpreter::initial()));
+        assert_ne!(Interpreter::initial(), with_parent);
+
+        assert_ne!(Interpreter::quote_string(), Interpreter::quote_symbol());
+    }
+
+    #[test]
+    fn programs_compare_their_interpreters() {
+        let program = |interp| Operation::Program(vec!['a'], Box::new(interp));
+        let mapping = Interpreter::mapping(Operation::intrinsic_mapping());
+
+        assert_eq!(program(Interpreter::initial()), program(mapping));
+        assert_ne!(
+            program(Interpreter::initial()),
+            program(Interpreter::quote_string())
+        );
+    }
+
+    #[test]
+    fn natives_are_equal_to_their_clones() {
+        let native = Native::new("native", |_| Ok(()));
+        let other = Native::new("native", |_| Ok(()));
+
+        assert_eq!(
+            Operation::Native(native.clone()),
+            Operation::Native(native.clone())
+        );
+        assert_ne!(Operation::Native(native), Operation::Native(other));
+    }
+
+    #[test]
+    fn equal_interpreters_are_deduplicated() {
+        let mut set = HashSet::new();
+        set.insert(Interpreter::initial());
+        set.insert(Interpreter::mapping(Operation::intrinsic_mapping()));
+        set.insert(Interpreter::quote_string());
+
+        assert_eq!(set.len(), 2);
+    }
+}
@@ -0,0 +1,290 @@
+//! Mascarpone as an `esolang_core` language, so it can be run by tools that support
+//! several languages.
+
+use std::{
+    io::{Read, Write},
+    iter, str,
+};
+
+use esolang_core::{
+    io::InputOutputPair,
+    language::{Execution, Frame, Inspect, Language, Scope},
+    limits::Limits,
+};
+
+use crate::{
+    encoding::{Encoded, Encoding},
+    profile::Profile,
+    state::{Element, State},
+    Error, Result, Symbol,
+};
+
+/// Runs programs with the given encoding, profile and limits. Programs are always read as
+/// UTF-8, whatever the encoding of their input and output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mascarpone {
+    pub encoding: Encoding,
+    pub profile: Profile,
+    pub limits: Limits,
+}
+
+impl Language for Mascarpone {
+    type Program = Vec<Symbol>;
+    type Error = Error;
+    type Execution<R: Read, W: Write> = Run<R, W>;
+
+    fn name(&self) -> &'static str {
+        "mascarpone"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["mascarpone"]
+    }
+
+    fn parse(&self, source: &[u8]) -> Result<Self::Program> {
+        match str::from_utf8(source) {
+            Ok(source) => Ok(source.chars().collect()),
+            Err(e) => {
+                let valid = &source[..e.valid_up_to()];
+                Err(Error::Syntax {
+                    line: valid.iter().filter(|&&b| b == b'\n').count() + 1,
+                    message: "program is not valid UTF-8".to_owned(),
+                })
+            }
+        }
+    }
+
+    fn start<R: Read, W: Write>(
+        &self,
+        program: Self::Program,
+        io: InputOutputPair<R, W>,
+    ) -> Self::Execution<R, W> {
+        let mut state =
+            State::new(Encoded::new(io, self.encoding), self.profile).with_limits(self.limits);
+        state.start(&program);
+        Run { state }
+    }
+
+    fn report(&self, error: &Error) -> String {
+        error.report().to_string()
+    }
+}
+
+/// A Mascarpone program being run, where each step interprets a single symbol.
+#[derive(Debug)]
+pub struct Run<R, W> {
+    state: State<Encoded<InputOutputPair<R, W>>>,
+}
+
+impl<R, W> Run<R, W> {
+    pub fn state(&self) -> &State<Encoded<InputOutputPair<R, W>>> {
+        &self.state
+    }
+}
+
+impl<R: Read, W: Write> Execution for Run<R, W> {
+    type Error = Error;
+    type IO = InputOutputPair<R, W>;
+
+    fn step(&mut self) -> Result<bool> {
+        self.state.step()
+    }
+
+    fn into_io(self) -> Self::IO {
+        self.state.into_io().into_inner()
+    }
+}
+
+impl<R, W> Inspect for Run<R, W> {
+    fn frames(&self) -> Vec<Frame> {
+        self.state
+            .call_stack()
+            .map(|(program, offset)| Frame {
+                offset,
+                len: program.len(),
+            })
+            .collect()
+    }
+
+    fn program(&self, depth: usize) -> Vec<char> {
+        self.state
+            .call_stack()
+            .nth(depth)
+            .map(|(program, _)| program.to_vec())
+            .unwrap_or_default()
+    }
+
+    fn scopes(&self) -> Vec<Scope> {
+        let stack = self
+            .state
+            .stack()
+            .enumerate()
+            .map(|(i, elem)| (i.to_string(), elem.to_string()))
+            .collect();
+
+        // The current interpreter, followed by its ancestors.
+        let tower = iter::successors(Some(&self.state.interpreter), |interp| interp.parent())
+            .enumerate()
+            .map(|(i, interp)| {
+                let elem = Element::Interpreter(Some(interp.clone()));
+                (i.to_string(), elem.to_string())
+            })
+            .collect();
+
+        vec![
+            Scope {
+                name: "Stack",
+                entries: stack,
+            },
+            Scope {
+                name: "Interpreters",
+                entries: tower,
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::VecDeque, io, rc::Rc};
+
+    use super::*;
+
+    /// Input that has to wait for more once it runs out, rather than end.
+    struct Pending(Rc<RefCell<VecDeque<u8>>>);
+
+    impl Read for Pending {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.borrow_mut().read(buf)? {
+                0 if !buf.is_empty() => Err(io::ErrorKind::WouldBlock.into()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    fn run(source: &[u8], input: &[u8]) -> Result<Vec<u8>> {
+        let language = Mascarpone::default();
+        let program = language.parse(source)?;
+        let mut execution = language.start(program, InputOutputPair::new(input, Vec::new()));
+        execution.run()?;
+        Ok(execution.into_io().output)
+    }
+
+    #[test]
+    fn run_program() {
+        assert_eq!(run(b"'o'l'l'e'h,......", b"!").unwrap(), b"!hello");
+    }
+
+    #[test]
+    fn step_program() {
+        let language = Mascarpone::default();
+        let program = language.parse(b"'a'b..").unwrap();
+        let mut execution = language.start(program, InputOutputPair::new(&b""[..], Vec::new()));
+
+        assert!(execution.step().unwrap());
+        assert!(execution.step().unwrap());
+        assert_eq!(execution.state().peek_element().unwrap().to_string(), "'a'");
+
+        execution.run().unwrap();
+        assert!(!execution.step().unwrap());
+        assert_eq!(execution.into_io().output, b"ba");
+    }
+
+    #[test]
+    fn step_over_tail_performed_operation() {
+        let language = Mascarpone::default();
+        // `p` performs `q` as the last thing it does, which replaces its frame.
+        let program = language.parse(b"v[.]v*'q<^v[q]v*'p<^'a'bp").unwrap();
+        let mut execution = language.start(program, InputOutputPair::new(&b""[..], Vec::new()));
+        while execution.frames().len() < 2 {
+            assert!(execution.step().unwrap());
+        }
+        assert_eq!(execution.frames()[1], Frame { offset: 0, len: 1 });
+        assert_eq!(execution.program(1), ['q']);
+
+        execution.step().unwrap();
+        assert_eq!(execution.program(1), ['.']);
+        // The started program stays the outermost frame, finished as it is.
+        assert_eq!(
+            execution.frames()[0],
+            Frame {
+                offset: 25,
+                len: 25
+            }
+        );
+        assert_eq!(execution.program(0).len(), 25);
+
+        execution.run().unwrap();
+        assert!(execution.frames().is_empty());
+        assert_eq!(execution.into_io().output, b"b");
+    }
+
+    #[test]
+    fn wait_for_input() {
+        let input = Rc::new(RefCell::new(VecDeque::new()));
+        let language = Mascarpone::default();
+        // Performs `,`, which reads a symbol, then outputs it.
+        let program = language.parse(b"v',>!.").unwrap();
+        let mut execution = language.start(
+            program,
+            InputOutputPair::new(Pending(Rc::clone(&input)), Vec::new()),
+        );
+
+        for _ in 0..4 {
+            assert!(execution.step().unwrap());
+        }
+        assert!(execution.step().unwrap_err().needs_input());
+        assert_eq!(execution.frames(), [Frame { offset: 4, len: 6 }]);
+
+        // Half of the symbol isn't enough, but isn't lost either.
+        input.borrow_mut().extend(&"é".as_bytes()[..1]);
+        assert!(execution.step().unwrap_err().needs_input());
+        assert_eq!(
+            execution.state().peek_element().unwrap().to_string(),
+            "<intrinsic Input>"
+        );
+
+        input.borrow_mut().extend(&"é".as_bytes()[1..]);
+        execution.run().unwrap();
+        assert_eq!(execution.into_io().output, "é".as_bytes());
+    }
+
+    #[test]
+    fn parse_invalid_utf8() {
+        let result = Mascarpone::default().parse(b"'a.\n'\xff.");
+        assert!(matches!(result, Err(Error::Syntax { line: 2, .. })));
+    }
+
+    #[test]
+    fn report_error() {
+        let error = run(b"'a..", b"").unwrap_err();
+        let report = Mascarpone::default().report(&error);
+
+        assert!(report.starts_with("error: unexpected empty stack"));
+        assert!(report.ends_with('\n'));
+    }
+
+    #[test]
+    fn inspect_run() {
+        let language = Mascarpone::default();
+        let program = language.parse(b"'a[]v'b").unwrap();
+        let mut execution = language.start(program, InputOutputPair::new(&b""[..], Vec::new()));
+        for _ in 0..5 {
+            execution.step().unwrap();
+        }
+
+        assert_eq!(execution.frames(), [Frame { offset: 5, len: 7 }]);
+        assert_eq!(execution.program(0).len(), 7);
+
+        let scopes = execution.scopes();
+        let stack = scopes[0].entries.iter().map(|(_, elem)| elem.as_str());
+        assert_eq!(
+            stack.collect::<Vec<_>>(),
+            ["<initial interpreter>", "']'", "'['", "'a'"]
+        );
+        assert_eq!(
+            scopes[1].entries,
+            [("0".to_owned(), "<initial interpreter>".to_owned())]
+        );
+    }
+}
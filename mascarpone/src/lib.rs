@@ -1,11 +1,31 @@
 use thiserror::Error;
 
-use std::io::{self, Read, Write};
+use std::{convert::Infallible, io};
 
-mod interpreter;
-mod operation;
+pub mod budget;
+pub mod builder;
+mod diagnostic;
+pub mod dot;
+pub mod encoding;
+pub mod interpreter;
+pub mod language;
+pub mod native;
+pub mod operation;
+pub mod prelude;
+pub mod profile;
+pub mod profiler;
+pub mod serialize;
 mod stack;
-mod state;
+pub mod state;
+
+use budget::Budget;
+pub use diagnostic::{Context, Location, Report};
+use encoding::{Encoded, Encoding};
+pub use esolang_core::io::InputOutputPair;
+use esolang_core::io::MemoryIO;
+use operation::Intrinsic;
+use profile::Profile;
+use state::ElementKind;
 
 pub type Symbol = char;
 
@@ -20,73 +40,315 @@ pub enum Error {
     NullInterpreter,
     #[error("unexpected empty stack")]
     EmptyStack,
-    #[error("expected a different type of element")]
-    WrongElementType,
+    #[error("expected {expected}, found {found}")]
+    WrongElementType {
+        expected: ElementKind,
+        found: ElementKind,
+    },
     #[error("expected a different interpreter variant")]
     WrongInterpreterVariant,
     #[error("tried popping a string without a closing delimiter")]
     MalformedString,
+    #[error("ran out of steps")]
+    OutOfBudget,
+    #[error("execution was cancelled")]
+    Cancelled,
+    #[error("stack grew past its limit of {limit} elements")]
+    StackLimit { limit: usize },
+    #[error("interpreter tower grew past its limit of {limit} interpreters")]
+    TowerLimit { limit: usize },
+    #[error("held on to more than {limit} symbols")]
+    SymbolLimit { limit: usize },
     #[error("error while performing IO")]
     IOError(#[from] std::io::Error),
+    /// The program read input that isn't there yet. The step that read it is
+    /// undone, so it can be taken again once there's input.
+    #[error("waiting for input")]
+    NeedsInput,
+    #[error("input is not valid {encoding}: {bytes:02x?}")]
+    InvalidInput { encoding: Encoding, bytes: Vec<u8> },
+    #[error("symbol {symbol:?} can't be represented in {encoding}")]
+    UnrepresentableSymbol { symbol: Symbol, encoding: Encoding },
+    #[error("syntax error on line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("{error}")]
+    Located {
+        error: Box<Error>,
+        context: Box<Context>,
+    },
 }
 
-pub type Result<T> = std::result::Result<T, Error>;
+impl Error {
+    /// The underlying error, with any location context stripped off.
+    pub fn root(&self) -> &Error {
+        match self {
+            Self::Located { error, .. } => error.root(),
+            _ => self,
+        }
+    }
 
-#[derive(Debug)]
-struct InputOutputPair<I, O> {
-    input: I,
-    output: O,
-}
+    /// Whether the program is only waiting for input, rather than failing.
+    pub fn needs_input(&self) -> bool {
+        matches!(self.root(), Self::NeedsInput)
+    }
 
-impl<I: Read, O> Read for InputOutputPair<I, O> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.input.read(buf)
+    pub fn context(&self) -> Option<&Context> {
+        match self {
+            Self::Located { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// A multi-line description of the error and where it happened, meant for
+    /// showing to a user.
+    pub fn report(&self) -> Report<'_> {
+        Report::new(self)
+    }
+
+    pub(crate) fn with_intrinsic(self, intrinsic: Intrinsic) -> Self {
+        match self {
+            Self::Located { .. } => self,
+            error => Self::Located {
+                error: Box::new(error),
+                context: Box::new(Context {
+                    intrinsic: Some(intrinsic),
+                    location: None,
+                }),
+            },
+        }
     }
-}
 
-impl<I, O: Write> Write for InputOutputPair<I, O> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.output.write(buf)
+    // Errors bubbling up out of nested programs already carry the innermost
+    // location, which is the one we're interested in, so we leave those alone.
+    pub(crate) fn with_location(self, location: impl FnOnce() -> Location) -> Self {
+        match self {
+            Self::Located { error, mut context } => {
+                if context.location.is_none() {
+                    context.location = Some(location());
+                }
+                Self::Located { error, context }
+            }
+            error => Self::Located {
+                error: Box::new(error),
+                context: Box::new(Context {
+                    intrinsic: None,
+                    location: Some(location()),
+                }),
+            },
+        }
     }
+}
 
-    fn flush(&mut self) -> io::Result<()> {
-        self.output.flush()
+// So that symbol IO that can't fail can be used with a state.
+impl From<Infallible> for Error {
+    fn from(never: Infallible) -> Self {
+        match never {}
     }
 }
 
+pub type Result<T> = std::result::Result<T, Error>;
+
 pub fn run_with_io<IO: io::Read + io::Write>(io: IO, program: &str) -> Result<()> {
-    let mut state = state::State::new(io);
+    run_with_io_encoding(io, program, Encoding::default())
+}
+
+pub fn run_with_io_encoding<IO: io::Read + io::Write>(
+    io: IO,
+    program: &str,
+    encoding: Encoding,
+) -> Result<()> {
+    let mut state = state::State::new(Encoded::new(io, encoding), Profile::default());
     let program = program.chars().collect::<Vec<_>>();
 
-    state.execute(program.as_slice())
+    state.execute(program.as_slice(), Budget::unlimited())
 }
 
 pub fn run(program: &str) -> Result<()> {
-    let io = InputOutputPair {
-        input: io::stdin(),
-        output: io::stdout(),
-    };
-    run_with_io(io, program)
+    run_with_encoding(program, Encoding::default())
+}
+
+/// Run `program` using standard input and output, which are read and written using
+/// `encoding`.
+pub fn run_with_encoding(program: &str, encoding: Encoding) -> Result<()> {
+    run_with_io_encoding(InputOutputPair::standard(), program, encoding)
 }
 
 pub fn compute(program: &str, input: &str) -> Result<String> {
-    let mut io = InputOutputPair {
-        input: io::Cursor::new(input),
-        output: Vec::<u8>::new(),
-    };
-    run_with_io(&mut io, program)?;
+    compute_with_budget(program, input, Budget::unlimited())
+}
 
-    Ok(String::from_utf8(io.output).expect("output should always be UTF-8"))
+pub fn compute_with_budget(program: &str, input: &str, budget: Budget) -> Result<String> {
+    let mut state = state::State::new(MemoryIO::new(input.chars()), Profile::default());
+    let program = program.chars().collect::<Vec<_>>();
+    state.execute(&program, budget)?;
+
+    Ok(state.into_io().collect_output())
 }
 
 #[cfg(test)]
 mod tests {
+    use esolang_core::limits::Limits;
+    use proptest::prelude::*;
+
     use super::*;
 
+    /// Programs made up mostly of symbols that mean something to the initial
+    /// interpreter, so they get past the first few symbols more often.
+    fn program() -> impl Strategy<Value = String> {
+        "[v^><{}*@!01\\['.,:$/a-c\\]]{0,64}"
+    }
+
     #[test]
     fn compute_simple_output() {
         let program = "[o[ll]eh].........";
 
         assert_eq!(compute(program, "").unwrap(), "]he]ll[o[");
     }
+
+    #[test]
+    fn compute_input_is_not_lost() {
+        let program = ",,..";
+
+        assert_eq!(compute(program, "ab").unwrap(), "ba");
+    }
+
+    #[test]
+    fn execute_with_prelude() {
+        let prelude = "(install \"p\" (program \"..\")) (install \"d\" (program \":p\"))"
+            .parse::<prelude::Prelude>()
+            .unwrap();
+        let mut state = state::State::new(MemoryIO::default(), Profile::default());
+        state.load_prelude(&prelude).unwrap();
+
+        state
+            .execute(&['\'', 'a', 'd'], Budget::unlimited())
+            .unwrap();
+
+        assert_eq!(state.into_io().into_output(), ['a', 'a']);
+    }
+
+    #[test]
+    fn compute_perform_program() {
+        let program = "'a[.]v*!";
+
+        assert_eq!(compute(program, "").unwrap(), "a");
+    }
+
+    #[test]
+    fn compute_perform_program_restores_interpreter() {
+        // The operation switches to an interpreter that outputs on every symbol.
+        let program = "[v'.>1^]v*!'b.";
+
+        assert_eq!(compute(program, "").unwrap(), "b");
+    }
+
+    #[test]
+    fn compute_expand_no_op() {
+        let program = "v'x>@$..";
+
+        assert_eq!(compute(program, "").unwrap(), "][");
+    }
+
+    #[test]
+    fn compute_unbounded_recursion_runs_out_of_budget() {
+        let error = compute_with_budget("[:!]v*:!", "", Budget::steps(100_000)).unwrap_err();
+
+        let location = error.context().unwrap().location.as_ref().unwrap();
+
+        assert!(matches!(error.root(), Error::OutOfBudget));
+        // The operation ends by performing itself, so its frame is replaced each
+        // time. The top-level program's frame stays below it.
+        assert_eq!(location.call_depth, 2);
+    }
+
+    #[test]
+    fn compute_deep_recursion_does_not_overflow() {
+        let error = compute_with_budget("[:!$]v*:!", "", Budget::steps(300_000)).unwrap_err();
+
+        assert!(matches!(error.root(), Error::OutOfBudget));
+        assert!(
+            error
+                .context()
+                .unwrap()
+                .location
+                .as_ref()
+                .unwrap()
+                .call_depth
+                > 10_000
+        );
+    }
+
+    #[test]
+    fn compute_error_has_location() {
+        let error = compute("'a'b<", "").unwrap_err();
+
+        assert!(matches!(
+            error.root(),
+            Error::WrongElementType {
+                expected: ElementKind::Operation,
+                found: ElementKind::Symbol,
+            }
+        ));
+
+        let context = error.context().unwrap();
+        assert_eq!(context.intrinsic, Some(Intrinsic::Install));
+
+        let location = context.location.as_ref().unwrap();
+        assert_eq!(location.offset, 4);
+        assert_eq!(location.symbol, '<');
+        assert_eq!(location.depth, 1);
+        assert_eq!(location.stack, Vec::<String>::new());
+    }
+
+    #[test]
+    fn compute_error_report() {
+        let error = compute("v'a'b<", "").unwrap_err();
+        let report = error.report().to_string();
+
+        assert_eq!(
+            report,
+            "error: expected operation, found symbol\n\
+             \x20 at offset 5, symbol '<'\n\
+             \x20 in intrinsic Install ('<')\n\
+             \x20 interpreter depth: 1\n\
+             \x20 call depth: 1\n\
+             \x20 stack (topmost first):\n\
+             \x20   <initial interpreter>\n"
+        );
+    }
+
+    proptest! {
+        // Programs can loop forever, so these have to be limited.
+        #[test]
+        fn execute_never_panics(program in program(), input in ".{0,8}") {
+            let mut state = state::State::new(MemoryIO::new(input.chars()), Profile::default());
+            let program = program.chars().collect::<Vec<_>>();
+
+            let _ = state.execute(&program, Budget::steps(10_000));
+        }
+
+        #[test]
+        fn execute_limited_never_panics(program in program(), input in ".{0,8}") {
+            let limits = Limits {
+                stack: Some(16),
+                tower: Some(4),
+                symbols: Some(64),
+                ..Limits::default()
+            };
+            let mut state = state::State::new(MemoryIO::new(input.chars()), Profile::default())
+                .with_limits(limits);
+            let program = program.chars().collect::<Vec<_>>();
+
+            let _ = state.execute(&program, Budget::steps(10_000));
+        }
+
+        #[test]
+        fn execute_never_panics_arbitrary(program in any::<String>()) {
+            let io = Encoded::new(InputOutputPair::new(io::empty(), io::sink()), Encoding::Utf8);
+            let mut state = state::State::new(io, Profile::default());
+            let program = program.chars().collect::<Vec<_>>();
+
+            let _ = state.execute(&program, Budget::steps(10_000));
+        }
+    }
 }
@@ -0,0 +1,77 @@
+use std::{env, fs, process};
+
+use mascarpone::{
+    budget::Budget,
+    encoding::{Encoded, Encoding},
+    prelude::Prelude,
+    profile::Profile,
+    state::State,
+    InputOutputPair,
+};
+
+const USAGE: &str = "usage: mascarpone [--encoding <encoding>] [--profile <profile>] \
+                     [--prelude <prelude>]... [--flamegraph <output>] <program>";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut encoding = Encoding::default();
+    let mut profile = Profile::default();
+    let mut preludes = Vec::new();
+    let mut flamegraph = None;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--encoding" => {
+                let name = args.next().unwrap_or_else(|| fail(USAGE));
+                encoding = name.parse().unwrap_or_else(|e: String| fail(&e));
+            }
+            "--profile" => {
+                let name = args.next().unwrap_or_else(|| fail(USAGE));
+                profile = name.parse().unwrap_or_else(|e: String| fail(&e));
+            }
+            "--prelude" => {
+                let path = args.next().unwrap_or_else(|| fail(USAGE));
+                let prelude = Prelude::load(&path)
+                    .unwrap_or_else(|e| fail(&format!("error: could not load {}: {}", path, e)));
+                preludes.push(prelude);
+            }
+            "--flamegraph" => flamegraph = Some(args.next().unwrap_or_else(|| fail(USAGE))),
+            _ if path.is_none() => path = Some(arg),
+            _ => fail(USAGE),
+        }
+    }
+
+    let path = path.unwrap_or_else(|| fail(USAGE));
+    let program = fs::read_to_string(&path)
+        .unwrap_or_else(|e| fail(&format!("error: could not read {}: {}", path, e)))
+        .chars()
+        .collect::<Vec<_>>();
+
+    let io = Encoded::new(InputOutputPair::standard(), encoding);
+    let mut state = State::new(io, profile);
+    if flamegraph.is_some() {
+        state = state.with_profiling();
+    }
+
+    let result = preludes
+        .iter()
+        .try_for_each(|prelude| state.load_prelude(prelude))
+        .and_then(|_| state.execute(&program, Budget::unlimited()));
+
+    if let (Some(path), Some(profiler)) = (&flamegraph, state.profiler()) {
+        fs::File::create(path)
+            .and_then(|mut file| profiler.write_folded(&mut file))
+            .unwrap_or_else(|e| fail(&format!("error: could not write {}: {}", path, e)));
+    }
+
+    if let Err(e) = result {
+        eprint!("{}", e.report());
+        process::exit(1);
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
@@ -0,0 +1,194 @@
+//! Operations implemented in Rust, so a host program can extend Mascarpone with
+//! things that can't be expressed in Mascarpone itself.
+
+use std::io;
+
+use esolang_core::{io::SymbolIO, native};
+
+use crate::{
+    interpreter::Interpreter,
+    language::Mascarpone,
+    operation::Operation,
+    state::{Element, State},
+    Error, Result, Symbol,
+};
+
+/// A named Rust closure that can be used as an operation.
+pub type Native = native::Native<Mascarpone>;
+
+impl native::Extensible for Mascarpone {
+    type Machine<'a> = dyn Machine + 'a;
+    type Error = Error;
+}
+
+/// The part of the interpreter state native operations have access to. This is
+/// implemented by `State`, but doesn't depend on the type of its IO, so native
+/// operations work with any of them. Symbols are read and written in the state's
+/// encoding.
+pub trait Machine: native::Machine<Symbol = Symbol, Error = Error> {
+    fn interpreter(&self) -> &Interpreter;
+
+    fn pop_element(&mut self) -> Result<Element>;
+    fn pop_symbol(&mut self) -> Result<Symbol>;
+    fn pop_operation(&mut self) -> Result<Operation>;
+    fn pop_interpreter_nullable(&mut self) -> Result<Option<Interpreter>>;
+    fn pop_string(&mut self) -> Result<Vec<Symbol>>;
+    fn peek_element(&self) -> Result<Element>;
+    fn push_element(&mut self, elem: Element);
+    fn push_string(&mut self, symbols: Vec<Symbol>);
+}
+
+impl<IO> Machine for State<IO>
+where
+    IO: SymbolIO<Symbol>,
+    Error: From<IO::Error>,
+{
+    fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    fn pop_element(&mut self) -> Result<Element> {
+        State::pop_element(self)
+    }
+
+    fn pop_symbol(&mut self) -> Result<Symbol> {
+        State::pop_symbol(self)
+    }
+
+    fn pop_operation(&mut self) -> Result<Operation> {
+        State::pop_operation(self)
+    }
+
+    fn pop_interpreter_nullable(&mut self) -> Result<Option<Interpreter>> {
+        State::pop_interpreter_nullable(self)
+    }
+
+    fn pop_string(&mut self) -> Result<Vec<Symbol>> {
+        State::pop_string(self)
+    }
+
+    fn peek_element(&self) -> Result<Element> {
+        State::peek_element(self)
+    }
+
+    fn push_element(&mut self, elem: Element) {
+        State::push_element(self, elem)
+    }
+
+    fn push_string(&mut self, symbols: Vec<Symbol>) {
+        State::push_string(self, symbols)
+    }
+}
+
+impl<IO> native::Machine for State<IO>
+where
+    IO: SymbolIO<Symbol>,
+    Error: From<IO::Error>,
+{
+    type Symbol = Symbol;
+    type Error = Error;
+
+    // Natives can't be undone, so they can't wait for input.
+    fn read_symbol(&mut self) -> Result<Symbol> {
+        match State::read_symbol(self) {
+            Err(Error::NeedsInput) => Err(io::Error::from(io::ErrorKind::WouldBlock).into()),
+            result => result,
+        }
+    }
+
+    fn write_symbol(&mut self, sym: Symbol) -> Result<()> {
+        State::write_symbol(self, sym)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use esolang_core::io::MemoryIO;
+
+    use super::*;
+    use crate::{budget::Budget, operation::Intrinsic, profile::Profile, Error};
+
+    fn execute(interp: Interpreter, program: &str, input: &str) -> Result<String> {
+        let mut state = State::new(MemoryIO::new(input.chars()), Profile::default());
+        state.interpreter = interp;
+        state.execute(&program.chars().collect::<Vec<_>>(), Budget::steps(1000))?;
+
+        Ok(state.into_io().collect_output())
+    }
+
+    fn uppercase() -> Operation {
+        Operation::Native(Native::new("uppercase", |machine| {
+            let sym = machine.pop_symbol()?;
+            for upper in sym.to_uppercase() {
+                machine.push_element(Element::Symbol(upper));
+            }
+            Ok(())
+        }))
+    }
+
+    #[test]
+    fn installed_native_operation() {
+        let mut interp = Interpreter::initial();
+        interp.install('u', uppercase()).unwrap();
+
+        assert_eq!(execute(interp, "'au.'ßu..", "").unwrap(), "ASS");
+    }
+
+    #[test]
+    fn performed_native_operation() {
+        let mut interp = Interpreter::initial();
+        interp.install('u', uppercase()).unwrap();
+
+        // Extract the operation and perform it.
+        assert_eq!(execute(interp, "'bv'u>!.", "").unwrap(), "B");
+    }
+
+    #[test]
+    fn native_operation_io() {
+        let echo = Native::new("echo", |machine| {
+            let sym = machine.read_symbol()?;
+            machine.write_symbol(sym)?;
+            machine.write_symbol(sym)
+        });
+        let interp = Interpreter::uniform(Operation::Native(echo));
+
+        assert_eq!(execute(interp, "xx", "ab").unwrap(), "aabb");
+    }
+
+    #[test]
+    fn expanded_native_operation_behaves_the_same() {
+        let mut interp = Interpreter::initial();
+        interp.install('u', uppercase()).unwrap();
+
+        assert_eq!(execute(interp, "'cv'u>@*!.", "").unwrap(), "C");
+    }
+
+    #[test]
+    fn native_operation_errors_are_located() {
+        let fail = Native::new("fail", |machine| {
+            machine.push_element(Element::Operation(Operation::Intrinsic(Intrinsic::NoOp)));
+            machine.pop_symbol().map(|_| ())
+        });
+        let mut interp = Interpreter::initial();
+        interp.install('f', Operation::Native(fail)).unwrap();
+
+        let error = execute(interp, "'a.f", "").unwrap_err();
+        assert!(matches!(error.root(), Error::WrongElementType { .. }));
+        assert_eq!(
+            error.context().unwrap().location.as_ref().unwrap().offset,
+            3
+        );
+    }
+
+    #[test]
+    fn native_operation_debug_shows_name() {
+        assert_eq!(
+            format!("{:?}", uppercase()),
+            "Native(Native(\"uppercase\"))"
+        );
+        assert_eq!(
+            Element::Operation(uppercase()).to_string(),
+            "<native uppercase>"
+        );
+    }
+}
@@ -1,25 +1,45 @@
-use std::{
-    collections::HashMap,
-    io::{Read, Write},
-};
+use std::{collections::HashMap, fmt};
+
+use esolang_core::io::SymbolIO;
 
 use crate::{
     interpreter::Interpreter,
+    native::Native,
+    profile::NullInterpreter,
     state::{Element, State},
     Error, Result, Symbol,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Operation {
     Intrinsic(Intrinsic),
     Program(Vec<Symbol>, Box<Interpreter>),
+    /// An operation implemented by the host program.
+    Native(Native),
 }
 
 impl Operation {
-    pub fn execute<IO: Read + Write>(&self, state: &mut State<IO>) -> Result<()> {
+    /// The number of symbols this operation holds on to: those of its program and
+    /// its interpreter, or just one if it has no program.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Program(program, interp) => program.len() + interp.size(),
+            Self::Intrinsic(_) | Self::Native(_) => 1,
+        }
+    }
+
+    pub fn execute<IO>(&self, state: &mut State<IO>) -> Result<()>
+    where
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
+    {
         match self {
             Self::Intrinsic(op) => op.execute(state),
-            Self::Program(program, interp) => todo!(),
+            Self::Program(program, interp) => {
+                state.perform_program(program, interp);
+                Ok(())
+            }
+            Self::Native(native) => native.call(state),
         }
     }
 
@@ -31,7 +51,29 @@ impl Operation {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// Only meant to give a short impression of an operation; programs in particular are
+// cut off if they get too long.
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const MAX_PROGRAM_LENGTH: usize = 16;
+
+        match self {
+            Self::Intrinsic(op) => write!(f, "intrinsic {:?}", op),
+            Self::Program(program, _) => {
+                let text = program.iter().take(MAX_PROGRAM_LENGTH).collect::<String>();
+                let ellipsis = if program.len() > MAX_PROGRAM_LENGTH {
+                    "..."
+                } else {
+                    ""
+                };
+                write!(f, "program {:?}{}", text, ellipsis)
+            }
+            Self::Native(native) => write!(f, "native {}", native.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Intrinsic {
     Reify,
     Deify,
@@ -76,6 +118,28 @@ impl Intrinsic {
         (Self::Swap, '/'),
     ];
 
+    /// `SYMBOLS`, sorted by symbol.
+    pub const SORTED_SYMBOLS: [(Self, Symbol); 18] = [
+        (Self::Perform, '!'),
+        (Self::Discard, '$'),
+        (Self::QuoteSymbol, '\''),
+        (Self::Create, '*'),
+        (Self::Input, ','),
+        (Self::Output, '.'),
+        (Self::Swap, '/'),
+        (Self::Null, '0'),
+        (Self::Uniform, '1'),
+        (Self::Dup, ':'),
+        (Self::Install, '<'),
+        (Self::Extract, '>'),
+        (Self::Expand, '@'),
+        (Self::QuoteString, '['),
+        (Self::Deify, '^'),
+        (Self::Reify, 'v'),
+        (Self::GetParent, '{'),
+        (Self::SetParent, '}'),
+    ];
+
     pub fn from_symbol(sym: Symbol) -> Option<Self> {
         Self::SYMBOLS
             .iter()
@@ -83,23 +147,71 @@ impl Intrinsic {
             .map(|&(op, _)| op)
     }
 
-    pub fn to_symbol(&self) -> Symbol {
+    /// The symbol the initial interpreter associates with this intrinsic. Only
+    /// `NoOp` doesn't have one.
+    pub fn to_symbol(self) -> Option<Symbol> {
         Self::SYMBOLS
             .iter()
-            .find(|&&(op, _)| op == *self)
+            .find(|&&(op, _)| op == self)
             .map(|&(_, sym)| sym)
-            .expect("intrisic operation needs an associated symbol")
     }
 
-    pub fn execute<IO: Read + Write>(&self, state: &mut State<IO>) -> Result<()> {
+    /// What the intrinsic does, in a sentence.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Reify => "Push the current interpreter.",
+            Self::Deify => "Pop an interpreter and make it the current one.",
+            Self::Extract => "Pop a symbol and an interpreter, and push the operation the interpreter associates with the symbol.",
+            Self::Install => "Pop a symbol, an operation and an interpreter, and push a copy of the interpreter that associates the symbol with the operation.",
+            Self::GetParent => "Pop an interpreter and push its parent.",
+            Self::SetParent => "Pop an interpreter and another, possibly null, one, and push the first with the second as its parent.",
+            Self::Create => "Pop an interpreter and a string, and push an operation that runs the string as a program using the interpreter.",
+            Self::Expand => "Pop an operation, and push its program as a string, followed by its interpreter.",
+            Self::Perform => "Pop an operation and perform it.",
+            Self::Null => "Push the null interpreter.",
+            Self::Uniform => "Pop an operation, and push an interpreter that associates every symbol with it.",
+            Self::QuoteString => "Push the symbols up to the matching `]` as a string, delimiters included.",
+            Self::QuoteSymbol => "Push the next symbol, without interpreting it.",
+            Self::Output => "Pop a symbol and output it.",
+            Self::Input => "Read a symbol and push it.",
+            Self::Dup => "Duplicate the element on top of the stack.",
+            Self::Discard => "Pop an element and throw it away.",
+            Self::Swap => "Swap the top two elements of the stack.",
+            Self::NoOp => "Do nothing.",
+        }
+    }
+
+    pub fn execute<IO>(&self, state: &mut State<IO>) -> Result<()>
+    where
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
+    {
+        if let Some(profiler) = state.profiler_mut() {
+            profiler.intrinsic(*self);
+        }
+
+        self.run(state).map_err(|e| e.with_intrinsic(*self))
+    }
+
+    fn run<IO>(&self, state: &mut State<IO>) -> Result<()>
+    where
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
+    {
         match self {
             Self::Reify => {
-                let interp = state.interpreter.clone();
-                state.push_element(Element::Interpreter(Some(interp)));
+                let interp = Some(state.interpreter.clone()).filter(|i| !i.is_null());
+                state.push_element(Element::Interpreter(interp));
                 Ok(())
             }
             Self::Deify => {
-                state.interpreter = state.pop_interpreter()?;
+                state.interpreter = match state.pop_interpreter_nullable()? {
+                    Some(interp) => interp,
+                    None => match state.profile().null_interpreter {
+                        NullInterpreter::FailOnDeify => return Err(Error::NullInterpreter),
+                        _ => Interpreter::null(),
+                    },
+                };
                 Ok(())
             }
             Self::Extract => {
@@ -121,14 +233,19 @@ impl Intrinsic {
             }
             Self::GetParent => {
                 let interpreter = state.pop_interpreter()?;
-                let parent = interpreter.parent().ok_or(Error::NoParent)?.clone();
-                state.push_element(Element::Interpreter(Some(parent)));
+                let parent = match interpreter.parent() {
+                    Some(parent) => Some(parent.clone()),
+                    None if state.profile().strict_parent => return Err(Error::NoParent),
+                    None => None,
+                };
+                state.push_element(Element::Interpreter(parent));
                 Ok(())
             }
             Self::SetParent => {
                 let (mut interp, new_parent) =
                     (state.pop_interpreter()?, state.pop_interpreter_nullable()?);
                 interp.set_parent(new_parent);
+                state.check_tower(&interp)?;
                 state.push_element(Element::Interpreter(Some(interp)));
                 Ok(())
             }
@@ -144,15 +261,31 @@ impl Intrinsic {
                 let op = state.pop_operation()?;
 
                 let (program, interp) = match op {
-                    Operation::Intrinsic(op) => (vec![op.to_symbol()], Interpreter::initial()),
+                    // A no-op has no symbol of its own, but the empty program does
+                    // exactly the same thing.
+                    Operation::Intrinsic(op) => {
+                        (op.to_symbol().into_iter().collect(), Interpreter::initial())
+                    }
                     Operation::Program(program, interp) => (program, *interp),
+                    // Any program of a single symbol will do, as long as the
+                    // interpreter performs the native operation for it.
+                    Operation::Native(native) => {
+                        (vec!['!'], Interpreter::uniform(Operation::Native(native)))
+                    }
                 };
 
                 state.push_string(program);
                 state.push_element(Element::Interpreter(Some(interp)));
                 Ok(())
             }
-            Self::Perform => state.pop_operation()?.execute(state),
+            Self::Perform => {
+                let op = state.pop_operation()?;
+                let result = op.execute(state);
+                if matches!(&result, Err(e) if e.needs_input()) {
+                    state.push_element(Element::Operation(op));
+                }
+                result
+            }
             Self::Null => {
                 state.push_element(Element::Interpreter(None));
                 Ok(())
@@ -183,15 +316,17 @@ impl Intrinsic {
                 Ok(())
             }
             Self::Dup => {
-                let elem = state.peek_element()?.clone();
+                let elem = state.peek_element()?;
                 state.push_element(elem);
                 Ok(())
             }
             Self::Discard => {
                 // The spec doesn't specify if discarding from an empty stack is an error,
-                // so I've decided to be strict in that case, for the time being.
-                let _ = state.pop_element()?;
-                Ok(())
+                // so this is up to the profile.
+                match state.pop_element() {
+                    Err(Error::EmptyStack) if !state.profile().strict_discard => Ok(()),
+                    result => result.map(|_| ()),
+                }
             }
             Self::Swap => {
                 let (a, b) = (state.pop_element()?, state.pop_element()?);
@@ -0,0 +1,100 @@
+use std::{fs, path::Path, str::FromStr};
+
+use crate::{interpreter::Interpreter, operation::Operation, serialize, Error, Result, Symbol};
+
+/// A set of operations to install into an interpreter before running a program,
+/// so programs don't have to define common operations themselves.
+///
+/// See the `serialize` module for the textual format of preludes.
+#[derive(Debug, Clone, Default)]
+pub struct Prelude {
+    definitions: Vec<(Symbol, Definition)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Definition {
+    Operation(Operation),
+    /// A program, which is interpreted by the interpreter the prelude is being
+    /// installed into, as it is right before this definition is installed.
+    Program(Vec<Symbol>),
+}
+
+impl Prelude {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Add a definition. Later definitions can build on earlier ones, or replace
+    /// them.
+    pub fn define(&mut self, sym: Symbol, def: Definition) {
+        self.definitions.push((sym, def));
+    }
+
+    pub fn definitions(&self) -> &[(Symbol, Definition)] {
+        &self.definitions
+    }
+
+    pub fn install_into(&self, interp: &mut Interpreter) -> Result<()> {
+        for (sym, def) in &self.definitions {
+            let op = match def {
+                Definition::Operation(op) => op.clone(),
+                Definition::Program(program) => {
+                    Operation::Program(program.clone(), Box::new(interp.clone()))
+                }
+            };
+            interp.install(*sym, op)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Prelude {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        serialize::read_prelude(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prelude_definitions_build_on_each_other() {
+        let prelude = "
+            ; Output the top two symbols.
+            (install \"p\" (program \"..\"))
+            ; Output a symbol twice.
+            (install \"d\" (program \":p\"))
+            (install \"s\" (intrinsic Swap))
+        "
+        .parse::<Prelude>()
+        .unwrap();
+
+        let mut interp = Interpreter::initial();
+        prelude.install_into(&mut interp).unwrap();
+
+        assert!(matches!(
+            interp.extract('d').unwrap(),
+            Operation::Program(..)
+        ));
+        assert_eq!(
+            interp.extract('s').unwrap(),
+            Operation::Intrinsic(crate::operation::Intrinsic::Swap)
+        );
+    }
+
+    #[test]
+    fn prelude_rejects_other_expressions() {
+        assert!(matches!(
+            "(install \"p\" (program \"..\"))\n(initial)".parse::<Prelude>(),
+            Err(Error::Syntax { line: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,148 @@
+use std::str::FromStr;
+
+/// Decides how to handle situations the spec doesn't clearly describe.
+///
+/// The defaults are those of `Profile::strict`, which make every questionable
+/// situation an error as early as possible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    /// Whether discarding from an empty stack is an error. If not, it does nothing.
+    pub strict_discard: bool,
+    /// What happens when the null interpreter is deified.
+    pub null_interpreter: NullInterpreter,
+    /// Whether getting the parent of an interpreter without one is an error. If not,
+    /// it gives the null interpreter.
+    pub strict_parent: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullInterpreter {
+    /// Deifying the null interpreter is an error.
+    FailOnDeify,
+    /// Deifying the null interpreter is fine, but it fails to interpret any symbol.
+    FailOnUse,
+    /// The null interpreter interprets every symbol as a no-op.
+    Ignore,
+}
+
+impl Profile {
+    pub fn strict() -> Self {
+        Self {
+            strict_discard: true,
+            null_interpreter: NullInterpreter::FailOnDeify,
+            strict_parent: true,
+        }
+    }
+
+    /// Behaves like the reference implementation, where every interpreter has a
+    /// parent, the initial interpreter's being the null interpreter, which can be
+    /// deified like any other.
+    pub fn reference() -> Self {
+        Self {
+            strict_discard: true,
+            null_interpreter: NullInterpreter::FailOnUse,
+            strict_parent: false,
+        }
+    }
+
+    /// Avoids errors wherever there's a sensible alternative.
+    pub fn lenient() -> Self {
+        Self {
+            strict_discard: false,
+            null_interpreter: NullInterpreter::Ignore,
+            strict_parent: false,
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::strict()),
+            "reference" => Ok(Self::reference()),
+            "lenient" => Ok(Self::lenient()),
+            _ => Err(format!("unknown profile: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use esolang_core::io::MemoryIO;
+
+    use super::*;
+    use crate::{budget::Budget, state::State, Error, Result};
+
+    fn execute(profile: Profile, program: &str) -> Result<String> {
+        let mut state = State::new(MemoryIO::default(), profile);
+        let program = program.chars().collect::<Vec<_>>();
+        state.execute(&program, Budget::steps(1000))?;
+
+        Ok(state.into_io().collect_output())
+    }
+
+    #[test]
+    fn strict_discard_fails_on_empty_stack() {
+        let error = execute(Profile::strict(), "$").unwrap_err();
+
+        assert!(matches!(error.root(), Error::EmptyStack));
+    }
+
+    #[test]
+    fn lenient_discard_ignores_empty_stack() {
+        assert_eq!(execute(Profile::lenient(), "$'a.").unwrap(), "a");
+    }
+
+    #[test]
+    fn null_interpreter_fail_on_deify() {
+        let error = execute(Profile::strict(), "0^").unwrap_err();
+
+        assert!(matches!(error.root(), Error::NullInterpreter));
+        assert_eq!(
+            error.context().unwrap().location.as_ref().unwrap().offset,
+            1
+        );
+    }
+
+    #[test]
+    fn null_interpreter_fail_on_use() {
+        let error = execute(Profile::reference(), "0^'a.").unwrap_err();
+
+        assert!(matches!(error.root(), Error::NullInterpreter));
+        assert_eq!(
+            error.context().unwrap().location.as_ref().unwrap().offset,
+            2
+        );
+    }
+
+    #[test]
+    fn null_interpreter_ignore() {
+        // Once the operation is done, the original interpreter is restored.
+        assert_eq!(execute(Profile::lenient(), "[0^'a.]v*!'b.").unwrap(), "b");
+    }
+
+    #[test]
+    fn strict_parent_fails_without_parent() {
+        let error = execute(Profile::strict(), "v{").unwrap_err();
+
+        assert!(matches!(error.root(), Error::NoParent));
+    }
+
+    #[test]
+    fn reference_parent_gives_null_interpreter() {
+        // Using the null interpreter as a parent is fine, but interpreting with it isn't.
+        assert!(execute(Profile::reference(), "v{v}$").is_ok());
+        assert!(matches!(
+            execute(Profile::reference(), "v{^'a").unwrap_err().root(),
+            Error::NullInterpreter
+        ));
+    }
+}
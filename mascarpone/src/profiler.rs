@@ -0,0 +1,293 @@
+//! Finding out where a program spends its time.
+//!
+//! Enable profiling with `State::with_profiling`, after which the state records how
+//! often each symbol is interpreted by each interpreter and how long that takes,
+//! how often each intrinsic is executed, and how long each performed program runs.
+//!
+//! Times are recorded as folded stacks as well, one frame per running program,
+//! which tools such as `flamegraph.pl` or `inferno` turn into flame graphs.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    iter,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    interpreter::Interpreter,
+    operation::{Intrinsic, Operation},
+    Symbol,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub count: u64,
+    pub time: Duration,
+}
+
+impl Stats {
+    fn record(&mut self, time: Duration) {
+        self.count += 1;
+        self.time += time;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    symbols: HashMap<Interpreter, HashMap<Symbol, Stats>>,
+    intrinsics: HashMap<Intrinsic, u64>,
+    /// Every program that was performed, indexed by `program_ids`.
+    programs: Vec<(Operation, Stats)>,
+    /// Programs are looked up by their symbols and the stable hash of their
+    /// interpreter, so that performing one doesn't mean cloning its tower.
+    program_ids: HashMap<Vec<Symbol>, HashMap<u64, usize>>,
+    /// The time spent on each symbol in each folded stack, indexed by `paths`.
+    folded: HashMap<(usize, Symbol), Duration>,
+    paths: Vec<String>,
+    /// Folded stacks by the one they extend, if any, and the program they add,
+    /// which is `None` for programs passed to `State::execute`.
+    path_ids: HashMap<(Option<usize>, Option<usize>), usize>,
+    frames: Vec<Frame>,
+}
+
+#[derive(Debug)]
+struct Frame {
+    /// The index of the program in `Profiler::programs`, or `None` for programs
+    /// passed to `State::execute`.
+    program: Option<usize>,
+    started: Instant,
+    /// The index of the folded stack ending in this frame in `Profiler::paths`.
+    path: usize,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How often, and for how long, each interpreter interpreted each symbol. The
+    /// time doesn't include that of any program that was performed as a result.
+    pub fn symbols(&self) -> impl Iterator<Item = (&Interpreter, Symbol, &Stats)> {
+        self.symbols.iter().flat_map(|(interp, symbols)| {
+            symbols
+                .iter()
+                .map(move |(&sym, stats)| (interp, sym, stats))
+        })
+    }
+
+    pub fn intrinsics(&self) -> &HashMap<Intrinsic, u64> {
+        &self.intrinsics
+    }
+
+    /// How often each program was performed, and how long it ran in total,
+    /// including the programs it performed in turn. A program that ends by
+    /// performing another one stops counting at that point.
+    pub fn programs(&self) -> impl Iterator<Item = (&Operation, &Stats)> {
+        self.programs.iter().map(|(op, stats)| (op, stats))
+    }
+
+    /// Write the time spent on each symbol as folded stacks, in nanoseconds.
+    pub fn write_folded(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut lines = self
+            .folded
+            .iter()
+            .map(|(&(path, sym), time)| {
+                let label = frame_label(&format!("{:?}", sym));
+                (format!("{};{}", self.paths[path], label), time)
+            })
+            .collect::<Vec<_>>();
+        lines.sort_unstable();
+
+        for (path, time) in lines {
+            writeln!(out, "{} {}", path, time.as_nanos())?;
+        }
+
+        Ok(())
+    }
+
+    /// Start timing `program` performed with `interpreter`, or a program passed to
+    /// `State::execute` if there's none.
+    pub(crate) fn enter(&mut self, program: Option<(&[Symbol], &Interpreter)>) {
+        let program = program.map(|(program, interp)| self.program_id(program, interp));
+        let parent = self.path();
+
+        let path = match self.path_ids.get(&(parent, program)) {
+            Some(&path) => path,
+            None => {
+                let label = match program {
+                    Some(program) => frame_label(&self.programs[program].0.to_string()),
+                    None => "execute".to_string(),
+                };
+                let path = match parent {
+                    Some(parent) => format!("{};{}", self.paths[parent], label),
+                    None => label,
+                };
+
+                self.paths.push(path);
+                self.path_ids
+                    .insert((parent, program), self.paths.len() - 1);
+                self.paths.len() - 1
+            }
+        };
+
+        self.frames.push(Frame {
+            program,
+            started: Instant::now(),
+            path,
+        });
+    }
+
+    fn program_id(&mut self, program: &[Symbol], interp: &Interpreter) -> usize {
+        let hash = interp.stable_hash();
+        if let Some(&id) = self.program_ids.get(program).and_then(|ids| ids.get(&hash)) {
+            return id;
+        }
+
+        let op = Operation::Program(program.to_vec(), Box::new(interp.clone()));
+        self.programs.push((op, Stats::default()));
+        self.program_ids
+            .entry(program.to_vec())
+            .or_default()
+            .insert(hash, self.programs.len() - 1);
+        self.programs.len() - 1
+    }
+
+    pub(crate) fn exit(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            if let Some(program) = frame.program {
+                self.programs[program].1.record(frame.started.elapsed());
+            }
+        }
+    }
+
+    /// Which folded stack of frames is running now, to be passed to `symbol`
+    /// later, as interpreting a symbol may enter or exit frames.
+    pub(crate) fn path(&self) -> Option<usize> {
+        self.frames.last().map(|frame| frame.path)
+    }
+
+    pub(crate) fn symbol(
+        &mut self,
+        path: Option<usize>,
+        interp: &Interpreter,
+        sym: Symbol,
+        time: Duration,
+    ) {
+        match self.symbols.get_mut(interp) {
+            Some(symbols) => symbols.entry(sym).or_default().record(time),
+            None => {
+                let mut stats = Stats::default();
+                stats.record(time);
+                self.symbols
+                    .insert(interp.clone(), iter::once((sym, stats)).collect());
+            }
+        }
+
+        if let Some(path) = path {
+            *self.folded.entry((path, sym)).or_default() += time;
+        }
+    }
+
+    pub(crate) fn intrinsic(&mut self, op: Intrinsic) {
+        *self.intrinsics.entry(op).or_default() += 1;
+    }
+}
+
+// Semicolons separate frames in folded stacks, so they can't appear in labels.
+fn frame_label(text: &str) -> String {
+    text.replace(';', "\\u{3b}")
+}
+
+#[cfg(test)]
+mod tests {
+    use esolang_core::io::MemoryIO;
+
+    use super::*;
+    use crate::{budget::Budget, profile::Profile, state::State};
+
+    fn profile(interp: Interpreter, program: &str) -> Profiler {
+        let mut state = State::new(MemoryIO::default(), Profile::default()).with_profiling();
+        state.interpreter = interp;
+        state
+            .execute(&program.chars().collect::<Vec<_>>(), Budget::steps(1000))
+            .unwrap();
+
+        state.take_profiler().unwrap()
+    }
+
+    fn output_twice() -> Interpreter {
+        let mut interp = Interpreter::initial();
+        interp
+            .install(
+                'd',
+                Operation::Program(":..".chars().collect(), Box::default()),
+            )
+            .unwrap();
+        interp
+    }
+
+    #[test]
+    fn profile_counts_intrinsics() {
+        let profiler = profile(Interpreter::initial(), "'a.'b.");
+
+        assert_eq!(profiler.intrinsics()[&Intrinsic::QuoteSymbol], 2);
+        assert_eq!(profiler.intrinsics()[&Intrinsic::Output], 2);
+        assert_eq!(profiler.intrinsics().len(), 2);
+    }
+
+    #[test]
+    fn profile_counts_symbols_per_interpreter() {
+        let profiler = profile(Interpreter::initial(), "'a.'b.");
+        let count = |interp: &Interpreter, sym| {
+            profiler
+                .symbols()
+                .find(|&(i, s, _)| i == interp && s == sym)
+                .map(|(_, _, stats)| stats.count)
+        };
+
+        assert_eq!(count(&Interpreter::initial(), '.'), Some(2));
+        assert_eq!(count(&Interpreter::initial(), 'a'), None);
+
+        let mut quote = Interpreter::quote_symbol();
+        quote.set_parent(Some(Interpreter::initial()));
+        assert_eq!(count(&quote, 'a'), Some(1));
+    }
+
+    #[test]
+    fn profile_counts_programs() {
+        let profiler = profile(output_twice(), "'ad'bd'cd");
+        let program = Operation::Program(":..".chars().collect(), Box::default());
+
+        let programs = profiler.programs().collect::<Vec<_>>();
+
+        assert_eq!(programs.len(), 1);
+        assert_eq!(programs[0].0, &program);
+        assert_eq!(programs[0].1.count, 3);
+    }
+
+    #[test]
+    fn profile_folded_stacks() {
+        let profiler = profile(output_twice(), "'ad'b.");
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+
+        let paths = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "execute;'.'",
+                "execute;'\\''",
+                "execute;'a'",
+                "execute;'b'",
+                "execute;'d'",
+                "execute;program \":..\";'.'",
+                "execute;program \":..\";':'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,539 @@
+//! A textual representation of interpreters and operations.
+//!
+//! The format consists of S-expressions, e.g. an interpreter that outputs every
+//! symbol, except `a`, which performs a program using the initial interpreter,
+//! with a parent:
+//!
+//! ```text
+//! (tower
+//!   (mapping
+//!     (default (intrinsic Output))
+//!     (install "a"
+//!       (program "'b." (initial))))
+//!   (initial))
+//! ```
+//!
+//! An interpreter with parents is written as a `tower` of it and its parents in
+//! order, rather than nesting them, since towers can get very deep.
+//!
+//! Mappings are always written in order of their symbols, so the same interpreter
+//! is always written the same way, which keeps differences between them readable.
+//! Anything after a `;` on a line is a comment.
+//!
+//! Preludes (see `Prelude`) are written as a series of `install` expressions, where
+//! the operation may also be written as just `(program "...")`, in which case it
+//! uses the interpreter defined by the prelude up to that point.
+//!
+//! Native operations are written as `(native "name")`, but since there's no way to
+//! get at their implementation, they can't be read back.
+
+use std::{collections::HashMap, fmt::Write, iter::Peekable, str::CharIndices};
+
+use crate::{
+    interpreter::{Interpreter, Variant},
+    operation::{Intrinsic, Operation},
+    prelude::{Definition, Prelude},
+    Error, Result, Symbol,
+};
+
+pub fn write_interpreter(interp: &Interpreter) -> String {
+    write_node(&interpreter_node(interp))
+}
+
+pub fn write_operation(op: &Operation) -> String {
+    write_node(&operation_node(op))
+}
+
+pub fn read_interpreter(text: &str) -> Result<Interpreter> {
+    node_interpreter(&Parser::new(text).parse_document()?)
+}
+
+pub fn read_operation(text: &str) -> Result<Operation> {
+    node_operation(&Parser::new(text).parse_document()?)
+}
+
+pub fn read_prelude(text: &str) -> Result<Prelude> {
+    let mut prelude = Prelude::new();
+
+    for node in Parser::new(text).parse_all()? {
+        match expect_list(&node, "install")? {
+            [sym, def] => {
+                let def = match split_list(def) {
+                    Some(("program", [Node::String(program, _)])) => {
+                        Definition::Program(program.chars().collect())
+                    }
+                    _ => Definition::Operation(node_operation(def)?),
+                };
+                prelude.define(node_symbol(sym)?, def);
+            }
+            _ => {
+                return Err(syntax_error(
+                    node.line(),
+                    "expected a symbol and an operation",
+                ))
+            }
+        }
+    }
+
+    Ok(prelude)
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Atom(String, usize),
+    String(String, usize),
+    List(Vec<Node>, usize),
+}
+
+impl Node {
+    fn atom(name: &str) -> Self {
+        Self::Atom(name.to_string(), 0)
+    }
+
+    fn string(s: impl Into<String>) -> Self {
+        Self::String(s.into(), 0)
+    }
+
+    fn list(head: &str, rest: impl IntoIterator<Item = Node>) -> Self {
+        Self::List(std::iter::once(Self::atom(head)).chain(rest).collect(), 0)
+    }
+
+    fn line(&self) -> usize {
+        match *self {
+            Self::Atom(_, line) | Self::String(_, line) | Self::List(_, line) => line,
+        }
+    }
+
+    // How deeply lists are nested within this node.
+    fn height(&self) -> usize {
+        match self {
+            Self::List(items, _) => 1 + items.iter().map(Node::height).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+fn interpreter_node(interp: &Interpreter) -> Node {
+    match interp.parent() {
+        Some(_) => Node::list("tower", interp.tower().map(|i| variant_node(i.variant()))),
+        None => variant_node(interp.variant()),
+    }
+}
+
+fn variant_node(variant: &Variant) -> Node {
+    match variant {
+        Variant::Null => Node::list("null", vec![]),
+        Variant::Initial => Node::list("initial", vec![]),
+        Variant::QuoteString => Node::list("quote-string", vec![]),
+        Variant::QuoteSymbol => Node::list("quote-symbol", vec![]),
+        Variant::Mapping { mapping, default } => {
+            let mut entries = mapping.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|&(&sym, _)| sym);
+
+            let mut items = vec![Node::list("default", vec![operation_node(default)])];
+            items.extend(entries.into_iter().map(|(&sym, op)| {
+                Node::list(
+                    "install",
+                    vec![Node::string(sym.to_string()), operation_node(op)],
+                )
+            }));
+            Node::list("mapping", items)
+        }
+    }
+}
+
+fn operation_node(op: &Operation) -> Node {
+    match op {
+        Operation::Intrinsic(op) => Node::list("intrinsic", vec![Node::atom(&format!("{:?}", op))]),
+        Operation::Native(native) => Node::list("native", vec![Node::string(native.name())]),
+        Operation::Program(program, interp) => Node::list(
+            "program",
+            vec![
+                Node::string(program.iter().collect::<String>()),
+                interpreter_node(interp),
+            ],
+        ),
+    }
+}
+
+fn write_node(node: &Node) -> String {
+    let mut out = String::new();
+    write_indented(&mut out, node, 0);
+    out.push('\n');
+    out
+}
+
+// Lists that only contain lists of atoms are written on a single line, others get a
+// line for every nested list.
+fn write_indented(out: &mut String, node: &Node, indent: usize) {
+    match node {
+        Node::Atom(name, _) => out.push_str(name),
+        Node::String(s, _) => {
+            let _ = write!(out, "{:?}", s);
+        }
+        Node::List(items, _) => {
+            let inline = node.height() <= 2;
+
+            out.push('(');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    if inline || !matches!(item, Node::List(..)) {
+                        out.push(' ');
+                    } else {
+                        out.push('\n');
+                        out.extend(std::iter::repeat_n(' ', indent + 2));
+                    }
+                }
+                write_indented(out, item, indent + 2);
+            }
+            out.push(')');
+        }
+    }
+}
+
+fn syntax_error(line: usize, message: impl Into<String>) -> Error {
+    Error::Syntax {
+        line,
+        message: message.into(),
+    }
+}
+
+// Split a list into the atom at its head and the rest of its items.
+fn split_list(node: &Node) -> Option<(&str, &[Node])> {
+    match node {
+        Node::List(items, _) => match items.split_first() {
+            Some((Node::Atom(head, _), rest)) => Some((head, rest)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn expect_list<'n>(node: &'n Node, head: &str) -> Result<&'n [Node]> {
+    match split_list(node) {
+        Some((name, rest)) if name == head => Ok(rest),
+        _ => Err(syntax_error(
+            node.line(),
+            format!("expected ({} ...)", head),
+        )),
+    }
+}
+
+fn node_interpreter(node: &Node) -> Result<Interpreter> {
+    match split_list(node) {
+        Some(("tower", [])) => Err(syntax_error(node.line(), "expected an interpreter")),
+        // Build the tower from the top down.
+        Some(("tower", tower)) => tower
+            .iter()
+            .rev()
+            .try_fold(None, |parent, node| {
+                let mut interp = node_variant(node).map(Interpreter::new)?;
+                interp.set_parent(parent);
+                Ok(Some(interp))
+            })
+            .map(|interp| interp.expect("the tower isn't empty")),
+        _ => node_variant(node).map(Interpreter::new),
+    }
+}
+
+fn node_variant(node: &Node) -> Result<Variant> {
+    let (head, rest) =
+        split_list(node).ok_or_else(|| syntax_error(node.line(), "expected an interpreter"))?;
+
+    match (head, rest) {
+        ("null", []) => Ok(Variant::Null),
+        ("initial", []) => Ok(Variant::Initial),
+        ("quote-string", []) => Ok(Variant::QuoteString),
+        ("quote-symbol", []) => Ok(Variant::QuoteSymbol),
+        ("mapping", [default, entries @ ..]) => {
+            let default = match expect_list(default, "default")? {
+                [op] => node_operation(op)?,
+                _ => return Err(syntax_error(default.line(), "expected a single operation")),
+            };
+
+            let mut mapping = HashMap::new();
+            for entry in entries {
+                match expect_list(entry, "install")? {
+                    [sym, op] => {
+                        mapping.insert(node_symbol(sym)?, node_operation(op)?);
+                    }
+                    _ => {
+                        return Err(syntax_error(
+                            entry.line(),
+                            "expected a symbol and an operation",
+                        ))
+                    }
+                }
+            }
+
+            Ok(Variant::Mapping { mapping, default })
+        }
+        _ => Err(syntax_error(node.line(), "expected an interpreter")),
+    }
+}
+
+fn node_operation(node: &Node) -> Result<Operation> {
+    if let Ok(rest) = expect_list(node, "intrinsic") {
+        return match rest {
+            [Node::Atom(name, _)] => intrinsic_from_name(name)
+                .map(Operation::Intrinsic)
+                .ok_or_else(|| syntax_error(node.line(), format!("unknown intrinsic {}", name))),
+            _ => Err(syntax_error(
+                node.line(),
+                "expected the name of an intrinsic",
+            )),
+        };
+    }
+
+    if expect_list(node, "native").is_ok() {
+        return Err(syntax_error(
+            node.line(),
+            "native operations can only be created by the host",
+        ));
+    }
+
+    match expect_list(node, "program") {
+        Ok([Node::String(program, _), interp]) => Ok(Operation::Program(
+            program.chars().collect(),
+            Box::new(node_interpreter(interp)?),
+        )),
+        _ => Err(syntax_error(node.line(), "expected an operation")),
+    }
+}
+
+fn node_symbol(node: &Node) -> Result<Symbol> {
+    if let Node::String(s, _) = node {
+        let mut chars = s.chars();
+        if let (Some(sym), None) = (chars.next(), chars.next()) {
+            return Ok(sym);
+        }
+    }
+
+    Err(syntax_error(node.line(), "expected a single symbol"))
+}
+
+fn intrinsic_from_name(name: &str) -> Option<Intrinsic> {
+    Intrinsic::SYMBOLS
+        .iter()
+        .map(|&(op, _)| op)
+        .chain(std::iter::once(Intrinsic::NoOp))
+        .find(|op| format!("{:?}", op) == name)
+}
+
+struct Parser<'t> {
+    chars: Peekable<CharIndices<'t>>,
+    line: usize,
+}
+
+impl<'t> Parser<'t> {
+    fn new(text: &'t str) -> Self {
+        Self {
+            chars: text.char_indices().peekable(),
+            line: 1,
+        }
+    }
+
+    fn parse_all(&mut self) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+
+        self.skip_whitespace();
+        while self.chars.peek().is_some() {
+            nodes.push(self.parse_node()?);
+            self.skip_whitespace();
+        }
+
+        Ok(nodes)
+    }
+
+    fn parse_document(&mut self) -> Result<Node> {
+        let node = self.parse_node()?;
+        self.skip_whitespace();
+
+        match self.chars.peek() {
+            None => Ok(node),
+            Some(_) => Err(syntax_error(self.line, "unexpected text after the end")),
+        }
+    }
+
+    fn parse_node(&mut self) -> Result<Node> {
+        self.skip_whitespace();
+        let line = self.line;
+
+        match self.chars.peek().map(|&(_, c)| c) {
+            None => Err(syntax_error(line, "unexpected end of text")),
+            Some(')') => Err(syntax_error(line, "unexpected ')'")),
+            Some('(') => {
+                self.chars.next();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.chars.peek() {
+                        Some(&(_, ')')) => {
+                            self.chars.next();
+                            return Ok(Node::List(items, line));
+                        }
+                        _ => items.push(self.parse_node()?),
+                    }
+                }
+            }
+            Some('"') => {
+                self.chars.next();
+                self.parse_string().map(|s| Node::String(s, line))
+            }
+            Some(_) => {
+                let mut name = String::new();
+                while let Some(&(_, c)) = self.chars.peek() {
+                    if c.is_whitespace() || "()\";".contains(c) {
+                        break;
+                    }
+                    name.push(c);
+                    self.chars.next();
+                }
+                Ok(Node::Atom(name, line))
+            }
+        }
+    }
+
+    // Parses the rest of a string after the opening quote, understanding the escapes
+    // `{:?}` produces.
+    fn parse_string(&mut self) -> Result<String> {
+        let mut string = String::new();
+
+        loop {
+            let c = self.next_char()?;
+            match c {
+                '"' => return Ok(string),
+                '\\' => string.push(match self.next_char()? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    '0' => '\0',
+                    c @ '\\' | c @ '"' | c @ '\'' => c,
+                    'u' => self.parse_unicode_escape()?,
+                    c => return Err(syntax_error(self.line, format!("unknown escape \\{}", c))),
+                }),
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        if self.next_char()? != '{' {
+            return Err(syntax_error(self.line, "expected '{' in unicode escape"));
+        }
+
+        let mut digits = String::new();
+        loop {
+            match self.next_char()? {
+                '}' => break,
+                c => digits.push(c),
+            }
+        }
+
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| syntax_error(self.line, format!("invalid unicode escape {}", digits)))
+    }
+
+    fn next_char(&mut self) -> Result<char> {
+        let (_, c) = self
+            .chars
+            .next()
+            .ok_or_else(|| syntax_error(self.line, "unterminated string"))?;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Ok(c)
+    }
+
+    // Also skips comments.
+    fn skip_whitespace(&mut self) {
+        let mut comment = false;
+
+        while let Some(&(_, c)) = self.chars.peek() {
+            match c {
+                '\n' => {
+                    self.line += 1;
+                    comment = false;
+                }
+                ';' => comment = true,
+                c if !(comment || c.is_whitespace()) => break,
+                _ => (),
+            }
+            self.chars.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Interpreter {
+        let program = Operation::Program("'b.".chars().collect(), Box::default());
+        let mut interp = Interpreter::uniform(Operation::Intrinsic(Intrinsic::Output));
+        interp.install('a', program).unwrap();
+        interp.set_parent(Some(Interpreter::initial()));
+        interp
+    }
+
+    #[test]
+    fn write_interpreter_example() {
+        assert_eq!(
+            write_interpreter(&example()),
+            "(tower\n  \
+               (mapping\n    \
+                 (default (intrinsic Output))\n    \
+                 (install \"a\"\n      \
+                   (program \"'b.\" (initial))))\n  \
+               (initial))\n"
+        );
+    }
+
+    #[test]
+    fn write_read_interpreter_roundtrips() {
+        let mut interp = Interpreter::initial();
+        interp
+            .install('"', Operation::Intrinsic(Intrinsic::NoOp))
+            .unwrap();
+        interp
+            .install(
+                '\n',
+                Operation::Program(vec!['\\', '\u{301}', '\0'], Box::new(example())),
+            )
+            .unwrap();
+        let mut quote = Interpreter::quote_string();
+        quote.set_parent(Some(interp));
+
+        let text = write_interpreter(&quote);
+
+        assert_eq!(write_interpreter(&read_interpreter(&text).unwrap()), text);
+    }
+
+    #[test]
+    fn write_read_deep_tower() {
+        let tower = (0..1_000_000).fold(Interpreter::initial(), |parent, _| {
+            let mut interp = Interpreter::quote_symbol();
+            interp.set_parent(Some(parent));
+            interp
+        });
+
+        assert_eq!(read_interpreter(&write_interpreter(&tower)).unwrap(), tower);
+    }
+
+    #[test]
+    fn write_read_operation_roundtrips() {
+        let op = Operation::Program(vec!['[', ']'], Box::new(example()));
+        let text = write_operation(&op);
+
+        assert_eq!(write_operation(&read_operation(&text).unwrap()), text);
+    }
+
+    #[test]
+    fn read_interpreter_reports_line() {
+        let error = read_interpreter("(mapping\n  (default (intrinsic Nope)))").unwrap_err();
+
+        assert!(matches!(error, Error::Syntax { line: 2, .. }));
+    }
+}
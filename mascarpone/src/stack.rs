@@ -1,76 +1,144 @@
-use std::iter;
+use std::{borrow::Cow, iter};
+
+use esolang_core::stack::Stack;
+
+use crate::{state::Element, Symbol, STRING_LEFT_DELIM, STRING_RIGHT_DELIM};
+
+/// A stack of elements that keeps runs of consecutive symbols together, so a string
+/// takes up a single entry instead of one per symbol. This is purely an
+/// optimization: symbols can still be pushed and popped one at a time.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ElementStack {
+    entries: Stack<Entry>,
+    len: usize,
+}
 
 #[derive(Debug, Clone)]
-pub struct Stack<T> {
-    storage: Vec<T>,
+enum Entry {
+    /// Never empty, and never directly on top of another run.
+    Symbols(Stack<Symbol>),
+    /// Never a symbol.
+    Other(Element),
 }
 
-impl<T> Stack<T> {
+impl ElementStack {
     pub fn new() -> Self {
-        Stack {
-            storage: Vec::new(),
-        }
+        Self::default()
     }
 
-    pub fn pop(&mut self) -> Option<T> {
-        self.storage.pop()
+    /// The number of elements, counting every symbol separately.
+    pub fn len(&self) -> usize {
+        self.len
     }
 
-    pub fn pop_while<'a, P>(&'a mut self, mut pred: P) -> impl Iterator<Item = T> + 'a
-    where
-        P: FnMut(&T) -> bool + 'a,
-    {
-        iter::from_fn(move || {
-            let x = self.pop()?;
-            if pred(&x) {
-                Some(x)
-            } else {
-                None
+    pub fn push(&mut self, elem: Element) {
+        match elem {
+            Element::Symbol(sym) => self.push_symbols(iter::once(sym)),
+            other => {
+                self.entries.push(Entry::Other(other));
+                self.len += 1;
             }
-        })
-    }
-
-    pub fn push(&mut self, value: T) {
-        self.storage.push(value)
+        }
     }
 
-    pub fn peek(&self) -> Option<&T> {
-        self.storage.last()
-    }
-}
+    pub fn push_symbols(&mut self, symbols: impl IntoIterator<Item = Symbol>) {
+        if !matches!(self.entries.peek(), Some(Entry::Symbols(_))) {
+            self.entries.push(Entry::Symbols(Stack::new()));
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        if let Some(Entry::Symbols(run)) = self.entries.peek_mut() {
+            let old_len = run.len();
+            run.extend(symbols);
+            self.len += run.len() - old_len;
 
-    #[test]
-    fn pop_while_empty() {
-        let mut stack = Stack::<u32>::new();
+            if run.is_empty() {
+                self.entries.pop();
+            }
+        }
+    }
 
-        let mut iter = stack.pop_while(|_| true);
+    pub fn pop(&mut self) -> Option<Element> {
+        let elem = match self.entries.peek_mut()? {
+            Entry::Symbols(run) => {
+                let sym = run.pop();
+                if run.is_empty() {
+                    self.entries.pop();
+                }
+                sym.map(Element::Symbol)
+            }
+            Entry::Other(_) => match self.entries.pop() {
+                Some(Entry::Other(elem)) => Some(elem),
+                _ => None,
+            },
+        };
 
-        assert_eq!(iter.next(), None)
+        self.len -= 1;
+        elem
     }
 
-    #[test]
-    fn pop_while_const_false() {
-        let mut stack = Stack {
-            storage: vec![1u32, 2, 3],
+    /// Pop the string on top of the stack at once, if all of it is there, returning
+    /// its symbols without the outer delimiters. Otherwise, nothing is popped.
+    pub fn pop_string(&mut self) -> Option<Vec<Symbol>> {
+        let string = match self.entries.peek_mut()? {
+            Entry::Symbols(run) => {
+                let string = run.pop_delimited(&STRING_LEFT_DELIM, &STRING_RIGHT_DELIM)?;
+                if run.is_empty() {
+                    self.entries.pop();
+                }
+                string
+            }
+            Entry::Other(_) => return None,
         };
 
-        let mut iter = stack.pop_while(|_| false);
+        self.len -= string.len() + 2;
+        Some(string)
+    }
 
-        assert_eq!(iter.next(), None)
+    pub fn peek(&self) -> Option<Cow<'_, Element>> {
+        self.iter().next()
     }
 
-    #[test]
-    fn pop_while() {
-        let mut stack = Stack {
-            storage: vec![1u32, 2, 3],
-        };
+    /// Iterate over the elements of the stack, from the top down.
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'_, Element>> {
+        self.entries.iter().rev().flat_map(|entry| {
+            let (run, other) = match entry {
+                Entry::Symbols(run) => (Some(run.iter()), None),
+                Entry::Other(elem) => (None, Some(Cow::Borrowed(elem))),
+            };
+
+            run.into_iter()
+                .flatten()
+                .rev()
+                .map(|&sym| Cow::Owned(Element::Symbol(sym)))
+                .chain(other)
+        })
+    }
+}
 
-        let iter = stack.pop_while(|&n| n > 1u32);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        assert_eq!(iter.collect::<Vec<_>>(), vec![3, 2])
+    #[test]
+    fn element_stack_keeps_symbols_together() {
+        let mut stack = ElementStack::new();
+        stack.push_symbols("a[b".chars());
+        stack.push(Element::Symbol(']'));
+        stack.push(Element::Interpreter(None));
+        stack.push_symbols("".chars());
+        stack.push(Element::Symbol('d'));
+
+        assert_eq!(stack.entries.len(), 3);
+        assert_eq!(stack.len(), 6);
+        assert_eq!(stack.pop_string(), None);
+
+        assert_eq!(stack.pop(), Some(Element::Symbol('d')));
+        assert_eq!(stack.pop(), Some(Element::Interpreter(None)));
+        assert_eq!(stack.pop_string(), Some(vec!['b']));
+        assert_eq!(stack.len(), 1);
+        assert_eq!(
+            stack.iter().collect::<Vec<_>>(),
+            [Cow::Owned(Element::Symbol('a'))]
+        );
     }
 }
@@ -1,49 +1,441 @@
-use std::{
-    collections::VecDeque,
-    io::{self, BufRead, BufReader, Read, Write},
-};
+use std::{borrow::Cow, collections::VecDeque, fmt, io, iter, time::Instant};
+
+use esolang_core::{io::SymbolIO, limits::Limits};
 
 use crate::{
-    interpreter::Interpreter, operation::Operation, stack::Stack, Error, Result, Symbol,
+    budget::Budget, interpreter::Interpreter, operation::Operation, prelude::Prelude,
+    profile::Profile, profiler::Profiler, stack::ElementStack, Error, Location, Result, Symbol,
     STRING_LEFT_DELIM, STRING_RIGHT_DELIM,
 };
 
 #[derive(Debug)]
 pub struct State<IO> {
-    stack: Stack<Element>,
+    stack: ElementStack,
     pub interpreter: Interpreter,
     io: IO,
+    profile: Profile,
+    budget: Budget,
+    limits: Limits,
+    /// The number of symbols held on to by the stack and `frames`, including the
+    /// interpreters that frames switch back to. The current interpreter changes too
+    /// often to keep track of, so it's weighed when the limit is checked. Only kept
+    /// track of if that's limited, as it's costly to work out the size of an
+    /// interpreter.
+    held: usize,
+    frames: Vec<Frame>,
+    profiler: Option<Profiler>,
 }
 
+/// A program that is being executed. Rather than recursing whenever an operation
+/// is performed, we keep track of these explicitly, so that a program can't
+/// overflow the native stack.
 #[derive(Debug, Clone)]
+struct Frame {
+    program: Vec<Symbol>,
+    /// The offset of the next symbol to interpret.
+    offset: usize,
+    /// The interpreter to switch back to once the program is done. This is `None`
+    /// for programs passed to `State::execute`, which just leave the interpreter as
+    /// it is.
+    caller: Option<Interpreter>,
+}
+
+impl Frame {
+    fn is_finished(&self) -> bool {
+        self.offset >= self.program.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Element {
     Symbol(Symbol),
     Operation(Operation),
     Interpreter(Option<Interpreter>),
 }
 
+impl Element {
+    pub fn kind(&self) -> ElementKind {
+        match self {
+            Self::Symbol(_) => ElementKind::Symbol,
+            Self::Operation(_) => ElementKind::Operation,
+            Self::Interpreter(_) => ElementKind::Interpreter,
+        }
+    }
+
+    /// The number of symbols this element holds on to, which is at least one.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Symbol(_) | Self::Interpreter(None) => 1,
+            Self::Operation(op) => op.size(),
+            Self::Interpreter(Some(interp)) => interp.size(),
+        }
+    }
+}
+
+// Only meant to give a short impression of an element, e.g. in error messages.
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Symbol(sym) => write!(f, "{:?}", sym),
+            Self::Operation(op) => write!(f, "<{}>", op),
+            Self::Interpreter(None) => write!(f, "<null interpreter>"),
+            Self::Interpreter(Some(interp)) => {
+                write!(f, "<{} interpreter>", interp.variant().name())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementKind {
+    Symbol,
+    Operation,
+    Interpreter,
+}
+
+impl fmt::Display for ElementKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Symbol => "symbol",
+            Self::Operation => "operation",
+            Self::Interpreter => "interpreter",
+        })
+    }
+}
+
 impl<IO> State<IO> {
-    pub fn new(io: IO) -> Self {
+    pub fn new(io: IO, profile: Profile) -> Self {
         Self {
-            stack: Stack::new(),
+            stack: ElementStack::new(),
             interpreter: Interpreter::default(),
             io,
+            profile,
+            budget: Budget::unlimited(),
+            limits: Limits::unlimited(),
+            held: 0,
+            frames: Vec::new(),
+            profiler: None,
+        }
+    }
+
+    /// Fail as soon as the program goes over one of `limits`. Mascarpone has no
+    /// queue, so there's no limit on that.
+    pub fn with_limits(self, limits: Limits) -> Self {
+        let mut state = Self { limits, ..self };
+        let stack = state.stack.iter().map(|elem| state.weigh(&elem));
+        let frames = state.frames.iter().map(|frame| state.weigh_frame(frame));
+        state.held = stack.chain(frames).sum();
+        state
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Record where execution spends its time. See the `profiler` module.
+    pub fn with_profiling(self) -> Self {
+        Self {
+            profiler: Some(Profiler::new()),
+            ..self
         }
     }
 
-    pub fn execute(&mut self, program: &[Symbol]) -> Result<()>
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub(crate) fn profiler_mut(&mut self) -> Option<&mut Profiler> {
+        self.profiler.as_mut()
+    }
+
+    /// Stop profiling, returning what has been recorded so far.
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Install the definitions of `prelude` into the current interpreter.
+    pub fn load_prelude(&mut self, prelude: &Prelude) -> Result<()> {
+        prelude.install_into(&mut self.interpreter)
+    }
+
+    pub fn io(&self) -> &IO {
+        &self.io
+    }
+
+    pub fn into_io(self) -> IO {
+        self.io
+    }
+
+    /// Interpret `program` with the current interpreter, for as long as `budget`
+    /// allows.
+    ///
+    /// If the budget runs out, or execution is cancelled, the state is left as it
+    /// was at that point, and can still be used to execute other programs.
+    pub fn execute(&mut self, program: &[Symbol], budget: Budget) -> Result<()>
     where
-        IO: Read + Write,
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
     {
-        for &sym in program {
-            self.interpreter.clone().interpret(sym, self)?
+        self.budget = budget;
+        self.run(program)
+    }
+
+    /// What's left of the budget given to the last call to `execute`.
+    pub fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
+    /// Start executing `program` with the current interpreter, without interpreting
+    /// any of it yet. Use `step` to do that.
+    pub fn start(&mut self, program: &[Symbol]) {
+        self.push_frame(Frame {
+            program: program.to_vec(),
+            offset: 0,
+            caller: None,
+        });
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(None);
+        }
+    }
+
+    /// Interpret the next symbol of the programs that have been started, returning
+    /// false if they're all done. Steps are taken from what's left of the budget.
+    ///
+    /// After an error, all programs that have been started are abandoned, except
+    /// when the program needs input that isn't there yet, which leaves the state
+    /// as it was before the step.
+    pub fn step(&mut self) -> Result<bool>
+    where
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
+    {
+        let result = self.step_above(0);
+        if matches!(&result, Err(e) if !e.needs_input()) {
+            self.unwind(0);
+        }
+
+        result
+    }
+
+    fn run(&mut self, program: &[Symbol]) -> Result<()>
+    where
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
+    {
+        let base = self.frames.len();
+        self.start(program);
+
+        let result = self.run_frames(base);
+        if result.is_err() {
+            self.unwind(base);
         }
 
+        result
+    }
+
+    // Run until all frames above `base` are done.
+    fn run_frames(&mut self, base: usize) -> Result<()>
+    where
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
+    {
+        while self.step_above(base)? {}
         Ok(())
     }
 
+    // Interpret the next symbol of the frames above `base`, returning false if they're
+    // all done. Frames that are done along the way are dropped.
+    fn step_above(&mut self, base: usize) -> Result<bool>
+    where
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
+    {
+        while self.frames.len() > base {
+            let frame = match self.frames.last_mut() {
+                Some(frame) => frame,
+                None => break,
+            };
+
+            let offset = frame.offset;
+            let sym = match frame.program.get(offset) {
+                Some(&sym) => sym,
+                None => {
+                    if let Some(caller) = self.pop_frame().and_then(|f| f.caller) {
+                        self.interpreter = caller;
+                    }
+                    if let Some(profiler) = &mut self.profiler {
+                        profiler.exit();
+                    }
+                    continue;
+                }
+            };
+            frame.offset += 1;
+
+            let interpreter = self.interpreter.clone();
+            let started = self.profiler.as_ref().map(|p| (p.path(), Instant::now()));
+            let depth = self.frames.len() - 1;
+            self.budget
+                .spend()
+                .and_then(|_| interpreter.interpret(sym, self))
+                .and_then(|_| self.check_limits())
+                .map_err(|e| {
+                    // Operations read before they change anything else, and perform
+                    // puts back the operation it popped, so only the step itself
+                    // needs undoing if there's no input yet.
+                    if e.needs_input() {
+                        self.frames[depth].offset = offset;
+                    }
+                    e.with_location(|| self.location(offset, sym, &interpreter))
+                })?;
+
+            if let (Some(profiler), Some((path, started))) = (&mut self.profiler, started) {
+                profiler.symbol(path, &interpreter, sym, started.elapsed());
+            }
+
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    // Drop all frames above `base` after an error, switching back to the interpreter
+    // that was in use before any of them were performed.
+    fn unwind(&mut self, base: usize) {
+        while self.frames.len() > base {
+            let frame = match self.pop_frame() {
+                Some(frame) => frame,
+                None => break,
+            };
+            if let Some(caller) = frame.caller {
+                self.interpreter = caller;
+            }
+            if let Some(profiler) = &mut self.profiler {
+                profiler.exit();
+            }
+        }
+    }
+
+    /// Start interpreting `program` using `interpreter`. Once it's done, the current
+    /// interpreter is restored.
+    ///
+    /// The program only starts running after the current symbol has been
+    /// interpreted completely, so this should be the last thing an operation does.
+    /// If the program that is currently running has no symbols left after this, its
+    /// frame is replaced by the new one, so that (mutually) tail-recursive
+    /// operations run in constant space. Programs that were started are never
+    /// replaced, so the outermost frame is always the program that was started.
+    pub fn perform_program(&mut self, program: &[Symbol], interpreter: &Interpreter) {
+        let tail_caller = match self.frames.last() {
+            Some(frame) if frame.is_finished() && frame.caller.is_some() => {
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.exit();
+                }
+                self.pop_frame().and_then(|f| f.caller)
+            }
+            _ => None,
+        };
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter(Some((program, interpreter)));
+        }
+        let current = std::mem::replace(&mut self.interpreter, interpreter.clone());
+
+        self.push_frame(Frame {
+            program: program.to_vec(),
+            offset: 0,
+            caller: Some(tail_caller.unwrap_or(current)),
+        });
+    }
+
+    fn push_frame(&mut self, frame: Frame) {
+        self.held += self.weigh_frame(&frame);
+        self.frames.push(frame);
+    }
+
+    fn pop_frame(&mut self) -> Option<Frame> {
+        let frame = self.frames.pop()?;
+        self.held -= self.weigh_frame(&frame);
+        Some(frame)
+    }
+
+    // The number of symbols `elem` adds to those held, if that's kept track of.
+    fn weigh(&self, elem: &Element) -> usize {
+        match self.limits.symbols {
+            Some(_) => elem.size(),
+            None => 0,
+        }
+    }
+
+    fn weigh_frame(&self, frame: &Frame) -> usize {
+        match self.limits.symbols {
+            Some(_) => frame.program.len() + frame.caller.as_ref().map_or(0, Interpreter::size),
+            None => 0,
+        }
+    }
+
+    fn check_limits(&self) -> Result<()> {
+        if let Some(limit) = Limits::exceeded(self.limits.stack, self.stack.len()) {
+            return Err(Error::StackLimit { limit });
+        }
+        if let Some(limit) = self.limits.symbols {
+            let held = self.held + self.interpreter.size();
+            if let Some(limit) = Limits::exceeded(Some(limit), held) {
+                return Err(Error::SymbolLimit { limit });
+            }
+        }
+        self.check_tower(&self.interpreter)
+    }
+
+    /// Fail if `interpreter`'s tower is higher than the limit.
+    pub fn check_tower(&self, interpreter: &Interpreter) -> Result<()> {
+        // No need to climb the whole tower if it's not limited.
+        let depth = match self.limits.tower {
+            Some(_) => interpreter.depth(),
+            None => return Ok(()),
+        };
+        match Limits::exceeded(self.limits.tower, depth) {
+            Some(limit) => Err(Error::TowerLimit { limit }),
+            None => Ok(()),
+        }
+    }
+
+    /// The number of programs currently being executed, including the ones passed to
+    /// `execute`.
+    pub fn call_depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The programs currently being executed, outermost first, along with the offset
+    /// of the next symbol to interpret in each.
+    pub fn call_stack(&self) -> impl Iterator<Item = (&[Symbol], usize)> {
+        self.frames
+            .iter()
+            .map(|frame| (frame.program.as_slice(), frame.offset))
+    }
+
+    fn location(&self, offset: usize, symbol: Symbol, interpreter: &Interpreter) -> Location {
+        Location {
+            offset,
+            symbol,
+            depth: interpreter.depth(),
+            call_depth: self.call_depth(),
+            stack: self
+                .stack
+                .iter()
+                .take(Location::STACK_SNAPSHOT_SIZE)
+                .map(|elem| elem.to_string())
+                .collect(),
+            stack_size: self.stack.len(),
+        }
+    }
+
     pub fn pop_element(&mut self) -> Result<Element> {
-        self.stack.pop().ok_or(Error::EmptyStack)
+        let elem = self.stack.pop().ok_or(Error::EmptyStack)?;
+        self.held -= self.weigh(&elem);
+        Ok(elem)
     }
 
     pub fn pop_interpreter(&mut self) -> Result<Interpreter> {
@@ -52,30 +444,47 @@ impl<IO> State<IO> {
     }
 
     pub fn pop_interpreter_nullable(&mut self) -> Result<Option<Interpreter>> {
-        if let Element::Interpreter(i) = self.pop_element()? {
-            Ok(i)
-        } else {
-            Err(Error::WrongElementType)
+        match self.pop_element()? {
+            Element::Interpreter(i) => Ok(i),
+            other => Err(Error::WrongElementType {
+                expected: ElementKind::Interpreter,
+                found: other.kind(),
+            }),
         }
     }
 
     pub fn pop_operation(&mut self) -> Result<Operation> {
-        if let Element::Operation(o) = self.pop_element()? {
-            Ok(o)
-        } else {
-            Err(Error::WrongElementType)
+        match self.pop_element()? {
+            Element::Operation(o) => Ok(o),
+            other => Err(Error::WrongElementType {
+                expected: ElementKind::Operation,
+                found: other.kind(),
+            }),
         }
     }
 
     pub fn pop_symbol(&mut self) -> Result<Symbol> {
-        if let Element::Symbol(s) = self.pop_element()? {
-            Ok(s)
-        } else {
-            Err(Error::WrongElementType)
+        match self.pop_element()? {
+            Element::Symbol(s) => Ok(s),
+            other => Err(Error::WrongElementType {
+                expected: ElementKind::Symbol,
+                found: other.kind(),
+            }),
         }
     }
 
     pub fn pop_string(&mut self) -> Result<Vec<Symbol>> {
+        // Usually the whole string is among the symbols on top of the stack, and we
+        // can take it at once. Otherwise, popping it symbol by symbol fails the same
+        // way it always has.
+        let len = self.stack.len();
+        if let Some(string) = self.stack.pop_string() {
+            if self.limits.symbols.is_some() {
+                self.held -= len - self.stack.len();
+            }
+            return Ok(string);
+        }
+
         let mut nesting = 0u32;
         let mut string = VecDeque::new();
 
@@ -102,19 +511,32 @@ impl<IO> State<IO> {
     }
 
     pub fn push_element(&mut self, elem: Element) {
+        self.held += self.weigh(&elem);
         self.stack.push(elem)
     }
 
     pub fn push_string(&mut self, symbols: impl IntoIterator<Item = Symbol>) {
-        self.push_element(Element::Symbol('['));
-        for sym in symbols {
-            self.push_element(Element::Symbol(sym));
+        let len = self.stack.len();
+        self.stack.push_symbols(
+            iter::once(STRING_LEFT_DELIM)
+                .chain(symbols)
+                .chain(iter::once(STRING_RIGHT_DELIM)),
+        );
+        if self.limits.symbols.is_some() {
+            self.held += self.stack.len() - len;
         }
-        self.push_element(Element::Symbol(']'));
     }
 
-    pub fn peek_element(&self) -> Result<&Element> {
-        self.stack.peek().ok_or(Error::EmptyStack)
+    pub fn peek_element(&self) -> Result<Element> {
+        self.stack
+            .peek()
+            .map(Cow::into_owned)
+            .ok_or(Error::EmptyStack)
+    }
+
+    /// Iterate over the elements on the stack, from the top down.
+    pub fn stack(&self) -> impl Iterator<Item = Cow<'_, Element>> {
+        self.stack.iter()
     }
 
     pub fn start_quote_string(&mut self) {
@@ -127,31 +549,46 @@ impl<IO> State<IO> {
         self.interpreter.set_parent(Some(old_interp));
     }
 
-    pub fn read_symbol(&mut self) -> io::Result<Symbol>
+    pub fn switch_to_parent(&mut self) -> Result<()> {
+        let parent = self.interpreter.parent().ok_or(Error::NoParent)?.clone();
+        self.interpreter = parent;
+        Ok(())
+    }
+
+    pub fn read_symbol(&mut self) -> Result<Symbol>
     where
-        IO: Read,
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
     {
-        use utf8_chars::BufReadCharsExt;
-
-        let mut buf_io = BufReader::with_capacity(4, &mut self.io);
-        buf_io
-            .read_char()?
-            .ok_or(io::ErrorKind::UnexpectedEof.into())
+        match self.io.read_symbol() {
+            Ok(Some(sym)) => Ok(sym),
+            Ok(None) => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Err(e) if IO::is_pending(&e) => Err(Error::NeedsInput),
+            Err(e) => Err(e.into()),
+        }
     }
 
-    pub fn write_symbol(&mut self, sym: Symbol) -> io::Result<()>
+    pub fn write_symbol(&mut self, sym: Symbol) -> Result<()>
     where
-        IO: Write,
+        IO: SymbolIO<Symbol>,
+        Error: From<IO::Error>,
     {
-        write!(self.io, "{}", sym)
+        Ok(self.io.write_symbol(sym)?)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    use esolang_core::io::MemoryIO;
     use proptest::prelude::*;
 
     use super::*;
+    use crate::interpreter::Variant;
 
     fn delimiterless_string() -> impl Strategy<Value = Vec<Symbol>> {
         any::<Vec<Symbol>>().prop_filter("symbol strings must not contain delimiters", |s| {
@@ -159,25 +596,256 @@ mod tests {
         })
     }
 
+    #[derive(Debug, Clone)]
+    enum StackOp {
+        Push(Element),
+        PushString(Vec<Symbol>),
+        Pop,
+        PopString,
+        Peek,
+    }
+
+    fn stack_symbol() -> impl Strategy<Value = Symbol> {
+        prop::sample::select(vec![STRING_LEFT_DELIM, STRING_RIGHT_DELIM, 'a', 'b'])
+    }
+
+    fn stack_op() -> impl Strategy<Value = StackOp> {
+        prop_oneof![
+            stack_symbol().prop_map(|sym| StackOp::Push(Element::Symbol(sym))),
+            Just(StackOp::Push(Element::Interpreter(None))),
+            prop::collection::vec(stack_symbol(), 0..8).prop_map(StackOp::PushString),
+            Just(StackOp::Pop),
+            Just(StackOp::PopString),
+            Just(StackOp::Peek),
+        ]
+    }
+
+    /// A plain stack of elements, with strings pushed and popped one symbol at a time,
+    /// which the state's stack has to be indistinguishable from.
+    #[derive(Debug, Default)]
+    struct ModelStack(Vec<Element>);
+
+    impl ModelStack {
+        fn pop_symbol(&mut self) -> Result<Symbol> {
+            match self.0.pop() {
+                Some(Element::Symbol(sym)) => Ok(sym),
+                Some(other) => Err(Error::WrongElementType {
+                    expected: ElementKind::Symbol,
+                    found: other.kind(),
+                }),
+                None => Err(Error::EmptyStack),
+            }
+        }
+
+        fn pop_string(&mut self) -> Result<Vec<Symbol>> {
+            let mut nesting = 0u32;
+            let mut string = VecDeque::new();
+
+            if self.pop_symbol()? != STRING_RIGHT_DELIM {
+                return Err(Error::MalformedString);
+            }
+
+            loop {
+                let sym = self.pop_symbol()?;
+                match sym {
+                    STRING_RIGHT_DELIM => nesting += 1,
+                    STRING_LEFT_DELIM if nesting == 0 => return Ok(string.into()),
+                    STRING_LEFT_DELIM => nesting -= 1,
+                    _ => (),
+                }
+
+                string.push_front(sym);
+            }
+        }
+    }
+
     #[test]
     fn pop_string_fails_on_empty_stack() {
-        let mut state = State::new(());
+        let mut state = State::new((), Profile::default());
 
         assert!(state.pop_string().is_err());
     }
 
     #[test]
     fn pop_string_fails_on_delimiterless() {
-        let mut state = State::new(());
+        let mut state = State::new((), Profile::default());
         state.push_element(Element::Symbol('a'));
 
         assert!(state.pop_string().is_err());
     }
 
+    #[test]
+    fn leaving_quote_without_parent_fails() {
+        let mut state = State::new(MemoryIO::default(), Profile::default());
+        state.push_element(Element::Interpreter(Some(Interpreter::quote_symbol())));
+
+        let error = state.execute(&['^', 'a'], Budget::unlimited()).unwrap_err();
+
+        assert!(matches!(error.root(), Error::NoParent));
+    }
+
+    #[test]
+    fn execute_out_of_budget() {
+        let mut state = State::new(MemoryIO::default(), Profile::default());
+        let program = "[:!]v*:!".chars().collect::<Vec<_>>();
+
+        let error = state.execute(&program, Budget::steps(50)).unwrap_err();
+
+        assert!(matches!(error.root(), Error::OutOfBudget));
+        assert_eq!(state.budget().remaining(), Some(0));
+        assert!(matches!(state.interpreter.variant(), Variant::Initial));
+        state.execute(&['$'], Budget::steps(1)).unwrap();
+    }
+
+    #[test]
+    fn execute_deep_tower_out_of_budget() {
+        let mut state = State::new(MemoryIO::default(), Profile::default());
+        // Keeps making the interpreter on the stack the parent of a new one.
+        let program = "v[/v}/:!]v*:!".chars().collect::<Vec<_>>();
+
+        let error = state
+            .execute(&program, Budget::steps(6_000_000))
+            .unwrap_err();
+        assert!(matches!(error.root(), Error::OutOfBudget));
+
+        let tower = state
+            .stack()
+            .find_map(|elem| match elem.into_owned() {
+                Element::Interpreter(Some(interp)) => Some(interp),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(tower.depth(), 999_999);
+        assert_eq!(tower.clone().depth(), tower.depth());
+    }
+
+    fn execute_limited(program: &str, limits: Limits) -> Result<()> {
+        let mut state = State::new(MemoryIO::default(), Profile::default()).with_limits(limits);
+        let program = program.chars().collect::<Vec<_>>();
+        state.execute(&program, Budget::steps(10_000))
+    }
+
+    #[test]
+    fn execute_over_limits() {
+        let stack = Limits {
+            stack: Some(2),
+            ..Limits::default()
+        };
+        let error = execute_limited("'a'b'c", stack).unwrap_err();
+        assert!(matches!(error.root(), Error::StackLimit { limit: 2 }));
+
+        // Every `[` within a string nests another quoting interpreter.
+        let tower = Limits {
+            tower: Some(3),
+            ..Limits::default()
+        };
+        let error = execute_limited("[[[", tower).unwrap_err();
+        assert!(matches!(error.root(), Error::TowerLimit { limit: 3 }));
+        let error = execute_limited("vv}v}v}", tower).unwrap_err();
+        assert!(matches!(error.root(), Error::TowerLimit { limit: 3 }));
+
+        // The program counts as well, along with the operations on the stack.
+        let symbols = Limits {
+            symbols: Some(8),
+            ..Limits::default()
+        };
+        let error = execute_limited("[abc]", symbols).unwrap_err();
+        assert!(matches!(error.root(), Error::SymbolLimit { limit: 8 }));
+        let symbols = Limits {
+            symbols: Some(14),
+            ..Limits::default()
+        };
+        let error = execute_limited("[ab]v*::", symbols).unwrap_err();
+        assert!(matches!(error.root(), Error::SymbolLimit { limit: 14 }));
+    }
+
+    #[test]
+    fn interpreters_count_towards_symbol_limit() {
+        let symbols = |limit| Limits {
+            symbols: Some(limit),
+            ..Limits::default()
+        };
+
+        // An interpreter is held on to while it's the current one, not just while
+        // it's on the stack.
+        execute_limited("v[ab]v*'x<$'a'b'c'd'e'f'g'h", symbols(51)).unwrap();
+        let error = execute_limited("v[ab]v*'x<^'a'b'c'd'e'f'g'h", symbols(51)).unwrap_err();
+        assert!(matches!(error.root(), Error::SymbolLimit { limit: 51 }));
+
+        // As is the interpreter that a performed program switches back to.
+        execute_limited("v[ab]v*'x<^'a'b'c'd'e'f'g'h", symbols(58)).unwrap();
+        let error = execute_limited("v[ab]v*'x<^v[]v*!", symbols(58)).unwrap_err();
+        assert!(matches!(error.root(), Error::SymbolLimit { limit: 58 }));
+    }
+
+    #[test]
+    fn execute_within_limits() {
+        let limits = Limits {
+            stack: Some(6),
+            tower: Some(2),
+            symbols: Some(20),
+            ..Limits::default()
+        };
+
+        // Recursion in constant space keeps going until the budget runs out.
+        let error = execute_limited("[:!]v*:!", limits).unwrap_err();
+        assert!(matches!(error.root(), Error::OutOfBudget));
+
+        let error = execute_limited("[:!$]v*:!", limits).unwrap_err();
+        assert!(matches!(error.root(), Error::SymbolLimit { limit: 20 }));
+    }
+
+    #[test]
+    fn execute_cancelled() {
+        let mut state = State::new(MemoryIO::default(), Profile::default());
+        let flag = Arc::new(AtomicBool::new(true));
+
+        let error = state
+            .execute(&['v'], Budget::unlimited().with_cancellation(flag.clone()))
+            .unwrap_err();
+        assert!(matches!(error.root(), Error::Cancelled));
+
+        flag.store(false, Ordering::Relaxed);
+        state
+            .execute(&['v'], Budget::unlimited().with_cancellation(flag))
+            .unwrap();
+    }
+
+    #[test]
+    fn step_through_program() {
+        let mut state = State::new(MemoryIO::default(), Profile::default());
+        state.start(&['\'', 'a', '[', '.', ']', 'v', '*', '!']);
+
+        let mut steps = 0;
+        while state.step().unwrap() {
+            steps += 1;
+            if steps == 2 {
+                assert_eq!(state.peek_element().unwrap(), Element::Symbol('a'));
+            }
+        }
+
+        // Every symbol, plus the one in the performed program.
+        assert_eq!(steps, 9);
+        assert!(!state.step().unwrap());
+        assert_eq!(state.into_io().into_output(), ['a']);
+    }
+
+    #[test]
+    fn step_error_abandons_programs() {
+        let mut state = State::new(MemoryIO::default(), Profile::default());
+        state.start(&['[', '$', '$', ']', 'v', '*', '!', '.']);
+
+        while state.step().is_ok() {}
+
+        assert_eq!(state.call_depth(), 0);
+        assert!(matches!(state.interpreter.variant(), Variant::Initial));
+        assert!(!state.step().unwrap());
+    }
+
     proptest! {
         #[test]
         fn push_string_pop_string_succeeds(string in delimiterless_string()) {
-            let mut state = State::new(());
+            let mut state = State::new((), Profile::default());
 
             state.push_string(string.clone());
             state.pop_string()?;
@@ -185,12 +853,51 @@ mod tests {
 
         #[test]
         fn push_string_pop_string_roundtrips(string in delimiterless_string()) {
-            let mut state = State::new(());
+            let mut state = State::new((), Profile::default());
 
             state.push_string(string.clone());
             let result = state.pop_string()?;
 
             prop_assert_eq!(result, string);
         }
+
+        #[test]
+        fn stack_behaves_like_plain_stack(ops in prop::collection::vec(stack_op(), 0..64)) {
+            let mut state = State::new((), Profile::default());
+            let mut model = ModelStack::default();
+
+            for op in ops {
+                let (actual, expected) = match op {
+                    StackOp::Push(elem) => {
+                        state.push_element(elem.clone());
+                        model.0.push(elem);
+                        continue;
+                    }
+                    StackOp::PushString(string) => {
+                        state.push_string(string.clone());
+                        model.0.push(Element::Symbol(STRING_LEFT_DELIM));
+                        model.0.extend(string.into_iter().map(Element::Symbol));
+                        model.0.push(Element::Symbol(STRING_RIGHT_DELIM));
+                        continue;
+                    }
+                    StackOp::Pop => (
+                        format!("{:?}", state.pop_element()),
+                        format!("{:?}", model.0.pop().ok_or(Error::EmptyStack)),
+                    ),
+                    StackOp::PopString => (
+                        format!("{:?}", state.pop_string()),
+                        format!("{:?}", model.pop_string()),
+                    ),
+                    StackOp::Peek => (
+                        format!("{:?}", state.peek_element()),
+                        format!("{:?}", model.0.last().cloned().ok_or(Error::EmptyStack)),
+                    ),
+                };
+
+                prop_assert_eq!(actual, expected);
+                prop_assert_eq!(state.stack.len(), model.0.len());
+                prop_assert!(state.stack.iter().eq(model.0.iter().rev().map(Cow::Borrowed)));
+            }
+        }
     }
 }